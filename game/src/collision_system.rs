@@ -0,0 +1,231 @@
+use rltk::{Point, PointF};
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        Balance, CargoDamage, Grinding, Name, Position, SufferDamage, Velocity, WantsToCollide,
+        WantsToFallover,
+    },
+    delivery_system::DeliverySystem,
+    gamelog::GameLog,
+    map::{Map, MAX_CLIMB},
+    physics_config::PhysicsConfig,
+};
+
+// running into a hostile at least this much faster than it is moving body
+// checks it instead of attacking it
+pub const BODY_CHECK_SPEED: f32 = 2.0;
+
+// fraction of the difference in velocity the faster entity hands over
+pub const VELOCITY_TRANSFER: f32 = 0.5;
+// lean from the impact per unit of relative speed
+pub const IMPACT_LEAN: f32 = 0.5;
+// damage per unit of relative speed
+pub const COLLISION_DAMAGE: f32 = 2.0;
+// the faster entity feels this fraction of the lean and damage
+pub const RECOIL: f32 = 0.5;
+
+pub struct CollisionSystem {}
+
+impl CollisionSystem {
+    pub fn impact_damage(speed: f32) -> i32 {
+        (speed * COLLISION_DAMAGE) as i32
+    }
+
+    fn clamp_speed(vel: PointF, max_speed: f32) -> PointF {
+        if vel.mag() > max_speed {
+            vel.normalized() * max_speed
+        } else {
+            vel
+        }
+    }
+}
+
+impl<'a> System<'a> for CollisionSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, Point>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, WantsToCollide>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Balance>,
+        WriteStorage<'a, Grinding>,
+        WriteStorage<'a, WantsToFallover>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, CargoDamage>,
+        ReadExpect<'a, PhysicsConfig>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut map,
+            mut player_pos,
+            player_entity,
+            mut log,
+            mut wants_collide,
+            names,
+            mut positions,
+            mut vels,
+            mut balances,
+            mut grinding,
+            mut fallovers,
+            mut damages,
+            mut cargo_damages,
+            config,
+        ) = data;
+
+        let collisions = (&entities, &wants_collide)
+            .join()
+            .map(|(entity, wants)| (entity, wants.target))
+            .collect::<Vec<_>>();
+
+        for (entity, target) in collisions {
+            let (pos, target_pos) = match (positions.get(entity), positions.get(target)) {
+                (Some(pos), Some(target_pos)) => (pos.point, target_pos.point),
+                _ => continue,
+            };
+            let (vel, target_vel) = match (vels.get(entity), vels.get(target)) {
+                (Some(vel), Some(target_vel)) => (vel.vel, target_vel.vel),
+                _ => continue,
+            };
+
+            // the faster entity knocks the slower one back, away from itself
+            let (fast, slow, fast_pos, slow_pos, fast_vel, slow_vel) =
+                if vel.mag() >= target_vel.mag() {
+                    (entity, target, pos, target_pos, vel, target_vel)
+                } else {
+                    (target, entity, target_pos, pos, target_vel, vel)
+                };
+            let speed = (fast_vel - slow_vel).mag();
+            let fast_physics = config.for_entity(names.get(fast));
+            let slow_physics = config.for_entity(names.get(slow));
+            let dir = PointF::new(
+                (slow_pos.x - fast_pos.x) as f32,
+                (slow_pos.y - fast_pos.y) as f32,
+            )
+            .normalized();
+
+            // part of the faster one's momentum carries over
+            let transfer = (fast_vel - slow_vel) * VELOCITY_TRANSFER;
+            if let Some(v) = vels.get_mut(fast) {
+                v.vel = CollisionSystem::clamp_speed(fast_vel - transfer, fast_physics.max_speed);
+            }
+            if let Some(v) = vels.get_mut(slow) {
+                v.vel = CollisionSystem::clamp_speed(slow_vel + transfer, slow_physics.max_speed);
+            }
+
+            // the impact throws both off balance
+            if let Some(b) = balances.get_mut(slow) {
+                b.bal += dir * speed * IMPACT_LEAN;
+            }
+            if let Some(b) = balances.get_mut(fast) {
+                b.bal -= dir * speed * IMPACT_LEAN * RECOIL;
+            }
+
+            let slow_damage = CollisionSystem::impact_damage(speed);
+            let fast_damage = CollisionSystem::impact_damage(speed * RECOIL);
+            if slow_damage > 0 {
+                SufferDamage::new_damage(&mut damages, slow, slow_damage);
+                CargoDamage::new_damage(
+                    &mut cargo_damages,
+                    slow,
+                    DeliverySystem::cargo_damage(speed),
+                );
+            }
+            if fast_damage > 0 {
+                SufferDamage::new_damage(&mut damages, fast, fast_damage);
+                CargoDamage::new_damage(
+                    &mut cargo_damages,
+                    fast,
+                    DeliverySystem::cargo_damage(speed * RECOIL),
+                );
+            }
+
+            let name = |e: Entity| {
+                names
+                    .get(e)
+                    .map_or("Something".to_string(), |n| n.name.clone())
+            };
+            if slow_damage > 0 {
+                log.entries.push(format!(
+                    "{} slams into {} for {} hp, taking {} hp",
+                    name(fast),
+                    name(slow),
+                    slow_damage,
+                    fast_damage
+                ));
+            } else {
+                log.entries
+                    .push(format!("{} bumps into {}", name(entity), name(target)));
+            }
+
+            // knocked back a tile, or into whatever is behind it
+            if speed > slow_physics.zero_speed {
+                let next = Point::new(
+                    slow_pos.x + dir.x.round() as i32,
+                    slow_pos.y + dir.y.round() as i32,
+                );
+                if next.x < 0
+                    || next.x > map.width - 1
+                    || next.y < 0
+                    || next.y > map.height - 1
+                    || map.blocked_tiles.contains(&next)
+                    || map.height_diff(slow_pos, next) > MAX_CLIMB
+                    || -map.height_diff(slow_pos, next) > MAX_CLIMB
+                {
+                    fallovers
+                        .insert(slow, WantsToFallover {})
+                        .expect("Could not insert intent to fallover");
+                    log.entries.push(format!("{} is knocked over", name(slow)));
+                } else if let Some(p) = positions.get_mut(slow) {
+                    map.blocked_tiles.remove(&p.point);
+                    p.point = next;
+                    map.blocked_tiles.insert(next);
+                    grinding.remove(slow);
+
+                    if slow == *player_entity {
+                        player_pos.x = next.x;
+                        player_pos.y = next.y;
+                    }
+                }
+            }
+
+            for (e, physics) in [(fast, fast_physics), (slow, slow_physics)] {
+                if balances
+                    .get(e)
+                    .is_some_and(|b| b.bal.mag() >= physics.fallover)
+                    && !fallovers.contains(e)
+                {
+                    fallovers
+                        .insert(e, WantsToFallover {})
+                        .expect("Could not insert intent to fallover");
+                    log.entries.push(format!("{} is knocked over", name(e)));
+                }
+            }
+        }
+
+        wants_collide.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impact_damage_grows_with_speed() {
+        assert_eq!(CollisionSystem::impact_damage(0.0), 0);
+        assert_eq!(CollisionSystem::impact_damage(1.5), 3);
+        assert_eq!(CollisionSystem::impact_damage(3.0), 6);
+    }
+
+    #[test]
+    fn gentle_bumps_do_no_damage() {
+        assert_eq!(CollisionSystem::impact_damage(0.4), 0);
+    }
+}
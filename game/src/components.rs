@@ -2,10 +2,17 @@ use std::collections::HashSet;
 
 use rltk::{Point, PointF, RGB};
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
+use std::convert::Infallible as NoError;
 
-#[derive(Component)]
+use crate::{
+    gamelog::GameLog, map::Map, physics_config::PhysicsConfig, replay::Replay, seed::WorldSeed,
+};
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
@@ -13,29 +20,29 @@ pub struct Renderable {
     pub render_order: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub point: Point,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Viewshed {
     pub visible_tiles: HashSet<rltk::Point>,
     pub range: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Monster {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Name {
     pub name: String,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct BlocksTile {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
@@ -43,12 +50,18 @@ pub struct CombatStats {
     pub power: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToMelee {
     pub target: Entity,
 }
 
-#[derive(Component, Debug)]
+// running into another moving entity this turn
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToCollide {
+    pub target: Entity,
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct SufferDamage {
     pub amount: Vec<i32>,
 }
@@ -66,66 +79,66 @@ impl SufferDamage {
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Item {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct ProvidesHealing {
     pub heal_amount: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct InBackpack {
     pub owner: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToPickUpItem {
     pub collected_by: Entity,
     pub item: Entity,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToUseItem {
     pub item: Entity,
     pub target: Option<Point>,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToDropItem {
     pub item: Entity,
 }
 
 // flag component => we delete it when we use it
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Consumable {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Ranged {
     pub range: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct InflictsDamage {
     pub damage: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct AreaOfEffect {
     pub radius: i32,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Velocity {
     pub vel: PointF,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Balance {
     pub bal: PointF,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct InstVel {
     pub vel: Vec<PointF>,
 }
@@ -142,5 +155,64 @@ impl InstVel {
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct WantsToFallover {}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Grinding {
+    pub dir: Point,
+}
+
+// an item that has to be carried to its destination
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct Package {
+    pub destination: Entity,
+    pub reward: i32,
+    pub condition: i32,
+}
+
+// where a package is dropped off
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryTarget {}
+
+// hits taken by whoever is carrying packages this turn
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct CargoDamage {
+    pub amount: Vec<i32>,
+}
+
+impl CargoDamage {
+    pub fn new_damage(store: &mut WriteStorage<CargoDamage>, carrier: Entity, amount: i32) {
+        if let Some(damage) = store.get_mut(carrier) {
+            damage.amount.push(amount);
+        } else {
+            store
+                .insert(
+                    carrier,
+                    CargoDamage {
+                        amount: vec![amount],
+                    },
+                )
+                .expect("Unable to insert cargo damage");
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Courier {
+    pub earnings: i32,
+    pub deliveries: i32,
+}
+
+// marks entities that get written out when saving
+pub struct SerializeMe;
+
+// carries the resources we save along with the entities
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub map: Map,
+    pub log: GameLog,
+    pub seed: WorldSeed,
+    pub physics: PhysicsConfig,
+    pub replay: Replay,
+}
@@ -0,0 +1,128 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{CargoDamage, Courier, InBackpack, Name, Package, Position},
+    gamelog::GameLog,
+};
+
+pub const MAX_CONDITION: i32 = 100;
+
+// every impact costs a package some condition, more the faster we were going
+pub const CARGO_BASE_DAMAGE: i32 = 5;
+pub const CARGO_SPEED_DAMAGE: f32 = 10.0;
+
+pub struct DeliverySystem {}
+
+impl DeliverySystem {
+    pub fn cargo_damage(speed: f32) -> i32 {
+        CARGO_BASE_DAMAGE + (speed * CARGO_SPEED_DAMAGE).round() as i32
+    }
+
+    // the reward shrinks with the package's condition
+    pub fn payout(package: &Package) -> i32 {
+        package.reward * package.condition / MAX_CONDITION
+    }
+}
+
+impl<'a> System<'a> for DeliverySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, Package>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Courier>,
+        WriteStorage<'a, CargoDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut log,
+            mut packages,
+            backpack,
+            positions,
+            names,
+            mut couriers,
+            mut cargo_damages,
+        ) = data;
+
+        for (package_entity, package, carried) in (&entities, &mut packages, &backpack).join() {
+            if let Some(damage) = cargo_damages.get(carried.owner) {
+                let sum_dmg: i32 = damage.amount.iter().sum();
+                package.condition = i32::max(0, package.condition - sum_dmg);
+
+                if carried.owner == *player_entity {
+                    log.entries.push(format!(
+                        "Your {} takes a knock, it is now in {}% condition",
+                        names.get(package_entity).unwrap().name,
+                        package.condition
+                    ));
+                }
+            }
+        }
+
+        cargo_damages.clear();
+
+        for (package_entity, package, carried) in (&entities, &packages, &backpack).join() {
+            let carrier_pos = positions.get(carried.owner);
+            let target_pos = positions.get(package.destination);
+
+            if let (Some(carrier_pos), Some(target_pos)) = (carrier_pos, target_pos) {
+                if carrier_pos.point != target_pos.point {
+                    continue;
+                }
+
+                let payout = DeliverySystem::payout(package);
+                if let Some(courier) = couriers.get_mut(carried.owner) {
+                    courier.earnings += payout;
+                    courier.deliveries += 1;
+                }
+
+                if carried.owner == *player_entity {
+                    log.entries.push(format!(
+                        "You deliver the {} in {}% condition and earn {} credits",
+                        names.get(package_entity).unwrap().name,
+                        package.condition,
+                        payout
+                    ));
+                }
+
+                entities
+                    .delete(package_entity)
+                    .expect("Unable to delete package");
+                entities
+                    .delete(package.destination)
+                    .expect("Unable to delete delivery target");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(condition: i32) -> Package {
+        let mut ecs = World::new();
+        Package {
+            destination: ecs.create_entity().build(),
+            reward: 40,
+            condition,
+        }
+    }
+
+    #[test]
+    fn intact_packages_pay_in_full() {
+        assert_eq!(DeliverySystem::payout(&package(MAX_CONDITION)), 40);
+    }
+
+    #[test]
+    fn damaged_packages_pay_less() {
+        assert_eq!(DeliverySystem::payout(&package(MAX_CONDITION / 2)), 20);
+        assert_eq!(DeliverySystem::payout(&package(0)), 0);
+    }
+}
@@ -1,11 +1,8 @@
 use rltk::{BError, BTerm};
 
-use specs::prelude::*;
-
 use crate::{
-    gui::MainMenuSelection,
     map::{MAPHEIGHT, MAPWIDTH},
-    state::{RunState, State},
+    state::State,
 };
 
 pub struct Game {
@@ -14,7 +11,7 @@ pub struct Game {
 }
 
 impl Game {
-    pub fn new() -> Result<Self, BError> {
+    pub fn new(state: State) -> Result<Self, BError> {
         use rltk::RltkBuilder;
 
         let try_context = RltkBuilder::simple80x50()
@@ -26,22 +23,6 @@ impl Game {
             Ok(context) => {
                 //context.with_post_scanlines(true);
 
-                let state = State {
-                    ecs: World::new(),
-                    map_drawn: false,
-                    redraw_menu: true,
-                    redraw_targeting: true,
-                    draw_inventory: false,
-                    look_cursor: (-1, -1),
-                    last_mouse_position: (-1, -1),
-                    mapgen_next_state: Some(RunState::MainMenu {
-                        menu_selection: MainMenuSelection::NewGame,
-                    }),
-                    mapgen_history: Vec::new(),
-                    mapgen_index: 0,
-                    mapgen_timer: 0.0,
-                };
-
                 Ok(Game { context, state })
             }
             Err(err) => Err(Err(err)),
@@ -52,13 +33,3 @@ impl Game {
         rltk::main_loop(game.context, game.state)
     }
 }
-
-impl Game {
-    pub fn register<T>(&mut self)
-    where
-        T: Component,
-        T::Storage: Default,
-    {
-        self.state.ecs.register::<T>();
-    }
-}
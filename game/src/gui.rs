@@ -4,10 +4,18 @@ use rltk::{Point, VirtualKeyCode, RGB};
 use specs::prelude::*;
 
 use crate::{
-    components::{AreaOfEffect, CombatStats, InBackpack, Name, Position, Viewshed},
+    components::{
+        AreaOfEffect, Balance, CombatStats, Courier, Grinding, InBackpack, Name, Package, Position,
+        Velocity, Viewshed,
+    },
+    delivery_system::DeliverySystem,
     gamelog::GameLog,
     map::Map,
+    movement_system::{Fall, MotionState, MovementSystem},
+    physics_config::PhysicsConfig,
     player::Player,
+    saveload_system,
+    seed::WorldSeed,
     state::{RunState, State},
 };
 
@@ -21,9 +29,40 @@ pub enum ItemMenuResult {
 #[derive(PartialEq, Clone, Copy)]
 pub enum MainMenuSelection {
     NewGame,
+    Continue,
+    SaveAndQuit,
     Quit,
 }
 
+// how many turns ahead the trajectory preview looks
+pub const PREVIEW_TURNS: i32 = 8;
+
+// what the trajectory preview assumes the player does from now on
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TrajectoryPreview {
+    Off,
+    Wait,
+    KeepPushing,
+}
+
+impl TrajectoryPreview {
+    pub fn next(&self) -> TrajectoryPreview {
+        match self {
+            TrajectoryPreview::Off => TrajectoryPreview::Wait,
+            TrajectoryPreview::Wait => TrajectoryPreview::KeepPushing,
+            TrajectoryPreview::KeepPushing => TrajectoryPreview::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrajectoryPreview::Off => "off",
+            TrajectoryPreview::Wait => "waiting",
+            TrajectoryPreview::KeepPushing => "pushing on",
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum MainMenuResult {
     NoSelection { selected: MainMenuSelection },
@@ -266,6 +305,17 @@ pub fn draw_ui(ecs: &World, ctx: &mut rltk::Rltk) {
         &depth,
     );
 
+    // the seed goes on the right, so a map can be reported and rebuilt
+    let seed = format!(" Seed: {} ", ecs.fetch::<WorldSeed>().seed);
+    let seed_x = 78 - seed.len() as i32;
+    ctx.print_color(
+        seed_x,
+        43,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &seed,
+    );
+
     let combat_stats = ecs.read_storage::<CombatStats>();
     let players = ecs.read_storage::<Player>();
     for (_player, stats) in (&players, &combat_stats).join() {
@@ -281,7 +331,7 @@ pub fn draw_ui(ecs: &World, ctx: &mut rltk::Rltk) {
         ctx.draw_bar_horizontal(
             28,
             43,
-            51,
+            seed_x - 29,
             stats.hp,
             stats.max_hp,
             RGB::named(rltk::RED),
@@ -289,6 +339,8 @@ pub fn draw_ui(ecs: &World, ctx: &mut rltk::Rltk) {
         );
     }
 
+    draw_job(ecs, ctx);
+
     let log = ecs.fetch::<GameLog>();
 
     let mut y = 44;
@@ -302,6 +354,137 @@ pub fn draw_ui(ecs: &World, ctx: &mut rltk::Rltk) {
     }
 }
 
+// numbers the tile the player ends each of the next few turns on if they
+// wait or keep pushing the way they're going, dotting the tiles crossed on
+// the way. Turns from white to red as they lean closer to falling, and the
+// tile where they would go down is marked ! for leaning too far or X for
+// running into something
+pub fn draw_trajectory(ecs: &World, ctx: &mut rltk::Rltk, preview: TrajectoryPreview) {
+    if preview == TrajectoryPreview::Off {
+        return;
+    }
+
+    let map = ecs.fetch::<Map>();
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let physics = ecs.fetch::<PhysicsConfig>().player();
+    let vels = ecs.read_storage::<Velocity>();
+    let balances = ecs.read_storage::<Balance>();
+    let grinding = ecs.read_storage::<Grinding>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+
+    let (vel, bal, viewshed) = match (
+        vels.get(*player_entity),
+        balances.get(*player_entity),
+        viewsheds.get(*player_entity),
+    ) {
+        (Some(vel), Some(bal), Some(viewshed)) => (vel.vel, bal.bal, viewshed),
+        _ => return,
+    };
+    let state = MotionState {
+        pos: *player_pos,
+        vel,
+        bal,
+        grind: grinding.get(*player_entity).map(|g| g.dir),
+    };
+
+    // keep pressing whichever of the eight directions we're moving closest to
+    let inst_vel = match preview {
+        TrajectoryPreview::KeepPushing if vel.mag() > 0.0 => {
+            let dir = vel.normalized();
+            let (dx, dy) = (dir.x.round(), dir.y.round());
+            Some(rltk::PointF::new(dx, dy).normalized() * physics.inst)
+        }
+        _ => None,
+    };
+
+    let (states, fall) =
+        MovementSystem::project_trajectory(&map, &physics, state, inst_vel, PREVIEW_TURNS);
+
+    let mut from = state.pos;
+    for (turn, next) in states.iter().enumerate() {
+        let lean = f32::min(next.bal.mag() / physics.fallover, 1.0);
+        let color = RGB::from_f32(1.0, 1.0 - lean, 1.0 - lean);
+        for point in rltk::line2d_bresenham(from, next.pos).into_iter().skip(1) {
+            if point == state.pos || !viewshed.visible_tiles.contains(&point) {
+                continue;
+            }
+            let glyph = if point == next.pos {
+                rltk::to_cp437(char::from_digit(turn as u32 + 1, 10).unwrap_or('+'))
+            } else {
+                rltk::to_cp437('∙')
+            };
+            ctx.set(point.x, point.y, color, RGB::named(rltk::BLACK), glyph);
+        }
+        from = next.pos;
+    }
+
+    if let Some(fall) = fall {
+        let (point, glyph) = match fall {
+            Fall::Balance(point) => (point, '!'),
+            Fall::Crash(point) => (point, 'X'),
+        };
+        if viewshed.visible_tiles.contains(&point) {
+            ctx.set(
+                point.x,
+                point.y,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::RED),
+                rltk::to_cp437(glyph),
+            );
+        }
+    }
+}
+
+fn draw_job(ecs: &World, ctx: &mut rltk::Rltk) {
+    let player_entity = ecs.fetch::<Entity>();
+    let packages = ecs.read_storage::<Package>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let names = ecs.read_storage::<Name>();
+    let couriers = ecs.read_storage::<Courier>();
+
+    let mut job = "Job: done, head for the stairs".to_string();
+    for (package_entity, package, name) in (&ecs.entities(), &packages, &names).join() {
+        let target_name = names.get(package.destination).unwrap();
+        match backpack.get(package_entity) {
+            Some(carried) if carried.owner == *player_entity => {
+                job = format!(
+                    "Job: deliver the {} ({}%) to the {} for {} credits",
+                    name.name,
+                    package.condition,
+                    target_name.name,
+                    DeliverySystem::payout(package)
+                );
+            }
+            _ => {
+                job = format!("Job: pick up the {}", name.name);
+            }
+        }
+    }
+
+    ctx.print_color(
+        2,
+        49,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &job,
+    );
+
+    if let Some(courier) = couriers.get(*player_entity) {
+        let earnings = format!(
+            " Earned: {} ({} delivered) ",
+            courier.earnings, courier.deliveries
+        );
+        ctx.print_color(
+            78 - earnings.len() as i32,
+            49,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &earnings,
+        );
+    }
+}
+
 pub fn draw_tooltips_mouse(ecs: &World, ctx: &mut rltk::Rltk) -> (i32, i32) {
     let mouse_pos = ctx.mouse_pos();
     ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
@@ -329,6 +512,12 @@ pub fn draw_tooltips_mouse(ecs: &World, ctx: &mut rltk::Rltk) -> (i32, i32) {
                 break;
             }
         }
+
+        if viewshed.visible_tiles.contains(&mouse_pos) {
+            if let Some(description) = map.describe_tile(mouse_pos.x, mouse_pos.y) {
+                tooltip.push(description);
+            }
+        }
     }
 
     if !tooltip.is_empty() {
@@ -443,6 +632,12 @@ pub fn draw_tooltips_xy(ecs: &World, ctx: &mut rltk::Rltk, xc: i32, yc: i32) {
                 break;
             }
         }
+
+        if viewshed.visible_tiles.contains(&Point::new(xc, yc)) {
+            if let Some(description) = map.describe_tile(xc, yc) {
+                tooltip.push(description);
+            }
+        }
     }
 
     if !tooltip.is_empty() {
@@ -629,6 +824,20 @@ pub fn ranged_target_selection(
     (ItemMenuResult::NoResponse, None)
 }
 
+// the entries that make sense right now, in the order they are shown
+fn main_menu_entries(gs: &State) -> Vec<(MainMenuSelection, &'static str)> {
+    let mut entries = vec![(MainMenuSelection::NewGame, "Begin New Game")];
+    if saveload_system::does_save_exist() {
+        entries.push((MainMenuSelection::Continue, "Continue"));
+    }
+    if gs.in_game {
+        entries.push((MainMenuSelection::SaveAndQuit, "Save & Quit"));
+    }
+    entries.push((MainMenuSelection::Quit, "Quit"));
+
+    entries
+}
+
 pub fn draw_main_menu(gs: &State, ctx: &mut rltk::Rltk) {
     let runstate = gs.ecs.fetch::<RunState>();
 
@@ -639,43 +848,38 @@ pub fn draw_main_menu(gs: &State, ctx: &mut rltk::Rltk) {
             RGB::named(rltk::BLACK),
             "Dangerous Deliveries",
         );
-        if menu_selection == MainMenuSelection::NewGame {
-            ctx.print_color_centered(
-                24,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        } else {
-            ctx.print_color_centered(
-                24,
-                RGB::named(rltk::WHITE),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        }
 
-        if menu_selection == MainMenuSelection::Quit {
-            ctx.print_color_centered(
-                26,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Quit",
-            );
-        } else {
-            ctx.print_color_centered(26, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+        let mut y = 24;
+        for (selection, text) in main_menu_entries(gs).iter() {
+            if menu_selection == *selection {
+                ctx.print_color_centered(
+                    y,
+                    RGB::named(rltk::MAGENTA),
+                    RGB::named(rltk::BLACK),
+                    text,
+                );
+            } else {
+                ctx.print_color_centered(y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), text);
+            }
+            y += 2;
         }
     }
 }
 
 pub fn process_main_menu(gs: &mut State, ctx: &mut rltk::Rltk) -> MainMenuResult {
+    let entries = main_menu_entries(gs);
     let runstate = gs.ecs.fetch::<RunState>();
 
     if let RunState::MainMenu { menu_selection } = *runstate {
+        let current = entries
+            .iter()
+            .position(|(selection, _)| *selection == menu_selection)
+            .unwrap_or(0);
+
         match ctx.key {
             None => {
                 return MainMenuResult::NoSelection {
-                    selected: menu_selection,
+                    selected: entries[current].0,
                 }
             }
             Some(key) => match key {
@@ -685,33 +889,25 @@ pub fn process_main_menu(gs: &mut State, ctx: &mut rltk::Rltk) -> MainMenuResult
                     }
                 }
                 VirtualKeyCode::W | VirtualKeyCode::K => {
-                    let newselection;
-                    match menu_selection {
-                        MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
-                        MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame,
-                    }
+                    let newselection = entries[(current + entries.len() - 1) % entries.len()].0;
                     return MainMenuResult::NoSelection {
                         selected: newselection,
                     };
                 }
                 VirtualKeyCode::S | VirtualKeyCode::J => {
-                    let newselection;
-                    match menu_selection {
-                        MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
-                        MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame,
-                    }
+                    let newselection = entries[(current + 1) % entries.len()].0;
                     return MainMenuResult::NoSelection {
                         selected: newselection,
                     };
                 }
                 VirtualKeyCode::Return => {
                     return MainMenuResult::Selected {
-                        selected: menu_selection,
+                        selected: entries[current].0,
                     }
                 }
                 _ => {
                     return MainMenuResult::NoSelection {
-                        selected: menu_selection,
+                        selected: entries[current].0,
                     }
                 }
             },
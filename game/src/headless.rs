@@ -0,0 +1,139 @@
+use std::env;
+use std::fs;
+
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::{
+    components::{CombatStats, Courier},
+    map::Map,
+    player::{self, PlayerAction},
+    seed::WorldSeed,
+    state::{RunState, State},
+};
+
+pub const HEADLESS_ARG: &str = "--headless";
+
+// `--headless <file>` points at a json list of player actions
+pub fn script_from_args() -> Option<Vec<PlayerAction>> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .iter()
+        .position(|a| a == HEADLESS_ARG)
+        .and_then(|i| args.get(i + 1))?;
+
+    let data = fs::read_to_string(path).expect("Unable to read headless script");
+    Some(serde_json::from_str(&data).expect("Unable to parse headless script"))
+}
+
+// builds a world for the seed and plays the actions through the same
+// systems the windowed game uses, handing back the world at the end
+pub fn run_headless(seed: WorldSeed, actions: &[PlayerAction]) -> World {
+    let mut state = State::new(seed);
+    state.generate_world_map(1);
+    play_actions(state, actions)
+}
+
+// plays the actions on whatever level the state already has
+pub fn play_actions(mut state: State, actions: &[PlayerAction]) -> World {
+    let mut runstate = state.tick_prerun();
+    for action in actions.iter() {
+        runstate = player::perform_action(&mut state.ecs, *action);
+
+        // resolve the turn until we are waiting on the player again
+        loop {
+            runstate = match runstate {
+                RunState::PlayerTurn => state.tick_player_turn(),
+                RunState::NextLevel => state.tick_next_level(),
+                RunState::PreRun => state.tick_prerun(),
+                _ => break,
+            };
+        }
+    }
+
+    *state.ecs.write_resource::<RunState>() = runstate;
+    state.ecs
+}
+
+pub fn run_script(seed: WorldSeed, actions: &[PlayerAction]) {
+    let ecs = run_headless(seed, actions);
+
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let stats = ecs.read_storage::<CombatStats>();
+    let couriers = ecs.read_storage::<Courier>();
+
+    println!("seed: {}", seed.seed);
+    println!("turns: {}", actions.len());
+    println!("depth: {}", map.depth);
+    println!("position: ({}, {})", player_pos.x, player_pos.y);
+    if let Some(stats) = stats.get(*player_entity) {
+        println!("hp: {} / {}", stats.hp, stats.max_hp);
+    }
+    if let Some(courier) = couriers.get(*player_entity) {
+        println!(
+            "earnings: {} ({} delivered)",
+            courier.earnings, courier.deliveries
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Position, map::TileType};
+
+    fn script() -> Vec<PlayerAction> {
+        let mut actions = vec![PlayerAction::Move { dx: 1, dy: 0 }; 2];
+        actions.extend([PlayerAction::Wait; 3]);
+        actions.push(PlayerAction::Move { dx: 0, dy: 1 });
+        actions.extend([PlayerAction::Wait; 3]);
+        actions
+    }
+
+    fn player_stats(ecs: &World) -> (i32, i32) {
+        let stats = ecs.read_storage::<CombatStats>();
+        let stats = stats.get(*ecs.fetch::<Entity>()).unwrap();
+        (stats.hp, stats.max_hp)
+    }
+
+    #[test]
+    fn same_seed_and_script_play_out_the_same() {
+        let seed = WorldSeed { seed: 3 };
+        let first = run_headless(seed, &script());
+        let second = run_headless(seed, &script());
+
+        assert_eq!(*first.fetch::<Point>(), *second.fetch::<Point>());
+        assert_eq!(first.fetch::<Map>().tiles, second.fetch::<Map>().tiles);
+        assert_eq!(player_stats(&first), player_stats(&second));
+    }
+
+    #[test]
+    fn scripted_moves_end_where_expected() {
+        // an empty room, so only the movement rules decide where we end up
+        let mut state = State::new(WorldSeed { seed: 42 });
+        let mut map = Map::new(1);
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let idx = map.xy_flat(x, y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+        let start = Point::new(10, 10);
+        state.ecs.insert(map);
+        state.ecs.insert(start);
+        let player = *state.ecs.fetch::<Entity>();
+        state
+            .ecs
+            .write_storage::<Position>()
+            .insert(player, Position { point: start })
+            .expect("Unable to place the player");
+
+        let ecs = play_actions(state, &script());
+
+        // two pushes east carry us four tiles, one south two more
+        let end = *ecs.fetch::<Point>();
+        assert_eq!((end.x - start.x, end.y - start.y), (4, 2));
+    }
+}
@@ -54,6 +54,18 @@ pub fn help_screen(ctx: &mut rltk::Rltk, width: u32, _height: u32) {
     left_col.push((String::from("Go Down Stairs"), None));
     right_col.push(String::from("0 (zero)"));
 
+    left_col.push((
+        String::from("Trajectory Preview (off, wait, push on)"),
+        None,
+    ));
+    right_col.push(String::from("T"));
+
+    left_col.push((String::from("Main Menu (Save & Quit)"), None));
+    right_col.push(String::from("M"));
+
+    left_col.push((String::from("Reload Physics (debug)"), None));
+    right_col.push(String::from("F5"));
+
     //left_col.push((String::from("Get Information or Give Package"), None));
     //right_col.push(String::from("G"));
 
@@ -157,6 +169,36 @@ pub fn help_screen(ctx: &mut rltk::Rltk, width: u32, _height: u32) {
 
     sc_y += 2;
 
+    ctx.print_color(
+        1,
+        sc_y,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        "Move onto a rail in its direction to grind it.",
+    );
+
+    sc_y += 2;
+
+    ctx.print_color(
+        1,
+        sc_y,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        "Deliver the package to its drop-off point to unlock the stairs.",
+    );
+
+    sc_y += 2;
+
+    ctx.print_color(
+        1,
+        sc_y,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        "Falling over or getting hit damages the package and lowers your pay.",
+    );
+
+    sc_y += 2;
+
     /*
     ctx.print_color(
         1,
@@ -1,33 +1,121 @@
 use std::collections::HashSet;
 
 use rltk::{console, Algorithm2D, BaseMap, Point, PointF, RGB};
+use serde::{Deserialize, Serialize};
 use specs::{Entity, Join, World, WorldExt};
 
 use crate::{
-    components::{Balance, CombatStats, Position, Velocity, Viewshed},
-    movement_system::{MovementSystem, BALANCE_DAMP, FALLOVER, PLAYER_INST, SPEED_DAMP},
+    components::{Balance, CombatStats, Grinding, Position, Velocity, Viewshed},
+    movement_system::{MotionState, MovementSystem},
+    physics_config::{Physics, PhysicsConfig},
     player::Player,
 };
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     DownStairs,
-    //Rail {xdir: i32, ydir: i32},
+    Rail { xdir: i32, ydir: i32 },
+}
+
+impl TileType {
+    pub fn rail_glyph(xdir: i32, ydir: i32) -> rltk::FontCharType {
+        match (xdir, ydir) {
+            (_, 0) => rltk::to_cp437('═'),
+            (0, _) => rltk::to_cp437('║'),
+            (x, y) if x == y => rltk::to_cp437('\\'),
+            _ => rltk::to_cp437('/'),
+        }
+    }
+
+    pub fn rail_name(xdir: i32, ydir: i32) -> &'static str {
+        match (xdir, ydir) {
+            (_, 0) => "Rail (east-west)",
+            (0, _) => "Rail (north-south)",
+            (x, y) if x == y => "Rail (northwest-southeast)",
+            _ => "Rail (northeast-southwest)",
+        }
+    }
+}
+
+// however slippery the floor, we keep losing a little speed and lean
+pub const MAX_SURFACE_DAMP: f32 = 0.95;
+
+// what the floor is covered in, changing how we slide and keep our balance
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Surface {
+    Plain,
+    Ice,
+    Mud,
+    Oil,
+    Grating,
+    Carpet,
+}
+
+impl Surface {
+    // what covers the floor scales how much speed and lean we keep from turn
+    // to turn and how far turning tips us, on top of our own physics. Plain
+    // floor leaves us be
+    pub fn apply(&self, physics: Physics) -> Physics {
+        let (speed_damp, balance_damp, lean_factor) = match self {
+            Surface::Plain => return physics,
+            Surface::Ice => (1.36, 1.5, 1.36),
+            Surface::Mud => (0.5, 1.2, 1.2),
+            Surface::Oil => (1.2, 1.6, 1.66),
+            Surface::Grating => (0.75, 0.8, 0.75),
+            Surface::Carpet => (0.75, 0.6, 0.6),
+        };
+        Physics {
+            speed_damp: f32::min(physics.speed_damp * speed_damp, MAX_SURFACE_DAMP),
+            balance_damp: f32::min(physics.balance_damp * balance_damp, MAX_SURFACE_DAMP),
+            lean_factor: physics.lean_factor * lean_factor,
+            ..physics
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Surface::Plain => "Floor",
+            Surface::Ice => "Ice",
+            Surface::Mud => "Mud",
+            Surface::Oil => "Oil slick",
+            Surface::Grating => "Grating",
+            Surface::Carpet => "Carpet",
+        }
+    }
+
+    pub fn color(&self) -> RGB {
+        match self {
+            Surface::Plain => RGB::from_f32(0.0, 0.0, 0.0),
+            Surface::Ice => RGB::from_f32(0.35, 0.55, 0.7),
+            Surface::Mud => RGB::from_f32(0.35, 0.22, 0.1),
+            Surface::Oil => RGB::from_f32(0.25, 0.1, 0.35),
+            Surface::Grating => RGB::from_f32(0.3, 0.3, 0.2),
+            Surface::Carpet => RGB::from_f32(0.45, 0.1, 0.1),
+        }
+    }
 }
 
 pub const MAPWIDTH: usize = 80;
 pub const MAPHEIGHT: usize = 50 - 6;
 pub const MAPCOUNT: usize = MAPHEIGHT * MAPWIDTH;
 
-#[derive(Clone)]
+pub const MAX_HEIGHT: i32 = 6;
+// an entity can step at most this many levels up or down without trouble
+pub const MAX_CLIMB: i32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<TileType>,
+    pub heights: Vec<i32>,
+    pub surfaces: Vec<Surface>,
     pub width: i32,
     pub height: i32,
     pub revealed_tiles: HashSet<Point>,
     pub blocked_tiles: HashSet<Point>,
+    // rebuilt by the map indexing system after loading
+    #[serde(skip)]
     pub tile_content: Vec<Vec<Entity>>,
     pub depth: i32,
     //pub render_table: HashMap<TileType, rltk::FontCharType>,
@@ -38,12 +126,82 @@ impl Map {
         y as usize * self.width as usize + x as usize
     }
 
-    fn is_exit_valid(&self, x: i32, y: i32) -> bool {
+    fn is_exit_valid(&self, from_idx: usize, x: i32, y: i32) -> bool {
         if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {
             return false;
         }
 
-        !self.blocked_tiles.contains(&Point::new(x, y))
+        // we can drop down any distance, but only climb so far
+        let climb = self.heights[self.xy_flat(x, y)] - self.heights[from_idx];
+        climb <= MAX_CLIMB && !self.blocked_tiles.contains(&Point::new(x, y))
+    }
+
+    pub fn surface_at(&self, point: Point) -> Surface {
+        self.surfaces[self.xy_flat(point.x, point.y)]
+    }
+
+    pub fn height_diff(&self, from: Point, to: Point) -> i32 {
+        self.heights[self.xy_flat(to.x, to.y)] - self.heights[self.xy_flat(from.x, from.y)]
+    }
+
+    pub fn is_safe_step(&self, from: Point, to: Point) -> bool {
+        self.height_diff(from, to).abs() <= MAX_CLIMB
+    }
+
+    // closest unblocked floor tile to the given point, searching outwards
+    // in rings of increasing radius
+    pub fn nearest_floor(&self, from: Point) -> Option<Point> {
+        let max_radius = i32::max(self.width, self.height);
+        for radius in 1..max_radius {
+            let mut closest: Option<(Point, f32)> = None;
+            for y in from.y - radius..=from.y + radius {
+                for x in from.x - radius..=from.x + radius {
+                    if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+                        continue;
+                    }
+
+                    let point = Point::new(x, y);
+                    if self.tiles[self.xy_flat(x, y)] != TileType::Floor
+                        || self.blocked_tiles.contains(&point)
+                    {
+                        continue;
+                    }
+
+                    let dist = rltk::DistanceAlg::Pythagoras.distance2d(from, point);
+                    if closest.is_none() || dist < closest.unwrap().1 {
+                        closest = Some((point, dist));
+                    }
+                }
+            }
+
+            if let Some((point, _dist)) = closest {
+                return Some(point);
+            }
+        }
+
+        None
+    }
+
+    pub fn describe_tile(&self, x: i32, y: i32) -> Option<String> {
+        if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+            return None;
+        }
+
+        let idx = self.xy_flat(x, y);
+        match self.tiles[idx] {
+            TileType::Rail { xdir, ydir } => Some(format!(
+                "{}, height {}",
+                TileType::rail_name(xdir, ydir),
+                self.heights[idx]
+            )),
+            TileType::Floor => Some(format!(
+                "{}, height {}",
+                self.surfaces[idx].name(),
+                self.heights[idx]
+            )),
+            TileType::DownStairs => Some(format!("Stairs, height {}", self.heights[idx])),
+            TileType::Wall => None,
+        }
     }
 }
 
@@ -75,39 +233,52 @@ pub fn cleanup_dead(ecs: &mut World) {
     }
 }
 
+// platforms are shaded lighter the higher they are
+fn get_height_color(height: i32, brightness: f32) -> RGB {
+    let shade = height as f32 / MAX_HEIGHT as f32 * 0.3 * brightness;
+    RGB::from_f32(shade, shade, shade)
+}
+
+// surfaces tint the floor they cover
+fn get_surface_color(surface: Surface, height: i32, brightness: f32) -> RGB {
+    let shade = get_height_color(height, brightness);
+    let tint = surface.color();
+    RGB::from_f32(
+        shade.r + tint.r * brightness,
+        shade.g + tint.g * brightness,
+        shade.b + tint.b * brightness,
+    )
+}
+
+// colours a tile by what pushing towards it would do this turn, played out
+// over every tile the move crosses. Blue to red as we lean closer to falling,
+// green if something on the way knocks us over
 fn get_simulation_color(
     map: &Map,
-    speed: &Velocity,
-    balance: &Balance,
-    player_pos: &Point,
+    physics: &Physics,
+    state: MotionState,
     map_pos: &Point,
 ) -> RGB {
-    let inst_v = PointF::new(
-        map_pos.x as f32 - player_pos.x as f32,
-        map_pos.y as f32 - player_pos.y as f32,
-    )
-    .normalized()
-        * PLAYER_INST;
-
-    let sim_x = (player_pos.x as f32 + speed.vel.x * SPEED_DAMP + inst_v.x)
-        .clamp(player_pos.x as f32 - 1.0, player_pos.x as f32 + 1.0)
-        .round() as i32;
-    let sim_y = (player_pos.y as f32 + speed.vel.y * SPEED_DAMP + inst_v.y)
-        .clamp(player_pos.y as f32 - 1.0, player_pos.y as f32 + 1.0)
-        .round() as i32;
-
-    let balance = balance.bal * BALANCE_DAMP;
-    let simulate_balance = MovementSystem::compute_balance(balance, speed.vel, inst_v);
-
-    let fallover = simulate_balance.mag() / FALLOVER;
-    let color: RGB;
-    if fallover < 1.0 && !map.blocked_tiles.contains(&Point::new(sim_x, sim_y)) {
-        color = RGB::from_f32(1.0 - fallover, 0.0, fallover);
+    let inst_v = if *map_pos == state.pos {
+        None
     } else {
-        color = RGB::from_f32(0.0, 1.0, 0.0);
+        Some(
+            PointF::new(
+                map_pos.x as f32 - state.pos.x as f32,
+                map_pos.y as f32 - state.pos.y as f32,
+            )
+            .normalized()
+                * physics.inst,
+        )
+    };
+
+    match MovementSystem::simulate_turn(map, physics, state, inst_v) {
+        Some(next) => {
+            let fallover = next.bal.mag() / physics.fallover;
+            RGB::from_f32(1.0 - fallover, 0.0, fallover)
+        }
+        None => RGB::from_f32(0.0, 1.0, 0.0),
     }
-
-    color
 }
 
 impl Map {
@@ -116,37 +287,58 @@ impl Map {
         let mut players = ecs.write_storage::<Player>();
         let balances = ecs.read_storage::<Balance>();
         let speeds = ecs.read_storage::<Velocity>();
+        let grinding = ecs.read_storage::<Grinding>();
         let player_pos = ecs.fetch::<Point>();
+        let physics = ecs.fetch::<PhysicsConfig>().player();
 
-        for (_player, viewshed, balance, speed) in
-            (&mut players, &mut viewsheds, &balances, &speeds).join()
+        for (_player, viewshed, balance, speed, grind) in
+            (&mut players, &mut viewsheds, &balances, &speeds, grinding.maybe()).join()
         {
+            let state = MotionState {
+                pos: *player_pos,
+                vel: speed.vel,
+                bal: balance.bal,
+                grind: grind.map(|g| g.dir),
+            };
             let mut x = 0;
             let mut y = 0;
 
             for tile in map.tiles.iter() {
                 let point = Point::new(x, y);
+                let height = map.heights[map.xy_flat(x, y)];
+                let surface = map.surfaces[map.xy_flat(x, y)];
                 if viewshed.visible_tiles.contains(&point) {
-                    let color = get_simulation_color(&map, &speed, &balance, &player_pos, &point);
+                    let color = get_simulation_color(map, &physics, state, &point);
+                    let bg = get_surface_color(surface, height, 1.0);
                     match tile {
                         TileType::Floor => {
-                            ctx.set(x, y, color, RGB::from_u8(0, 0, 0), rltk::to_cp437('.'));
+                            ctx.set(x, y, color, bg, rltk::to_cp437('.'));
                         }
                         TileType::Wall => {
                             ctx.set(x, y, color, RGB::from_u8(0, 0, 0), rltk::to_cp437('#'));
                         }
                         TileType::DownStairs => {
-                            ctx.set(x, y, color, RGB::from_u8(0, 0, 0), rltk::to_cp437('>'));
+                            ctx.set(x, y, color, bg, rltk::to_cp437('>'));
+                        }
+                        TileType::Rail { xdir, ydir } => {
+                            ctx.set(
+                                x,
+                                y,
+                                color,
+                                bg,
+                                TileType::rail_glyph(*xdir, *ydir),
+                            );
                         }
                     }
                 } else if map.revealed_tiles.contains(&point) {
+                    let bg = get_surface_color(surface, height, 0.5);
                     match tile {
                         TileType::Floor => {
                             ctx.set(
                                 x,
                                 y,
                                 RGB::from_u8(64, 64, 64),
-                                RGB::from_u8(0, 0, 0),
+                                bg,
                                 rltk::to_cp437('.'),
                             );
                         }
@@ -164,10 +356,19 @@ impl Map {
                                 x,
                                 y,
                                 RGB::from_u8(64, 64, 64),
-                                RGB::from_u8(0, 0, 0),
+                                bg,
                                 rltk::to_cp437('>'),
                             );
                         }
+                        TileType::Rail { xdir, ydir } => {
+                            ctx.set(
+                                x,
+                                y,
+                                RGB::from_u8(64, 64, 64),
+                                bg,
+                                TileType::rail_glyph(*xdir, *ydir),
+                            );
+                        }
                     }
                 }
 
@@ -199,6 +400,8 @@ impl Map {
     pub fn new(new_depth: i32) -> Map {
         Map {
             tiles: vec![TileType::Wall; MAPCOUNT],
+            heights: vec![0; MAPCOUNT],
+            surfaces: vec![Surface::Plain; MAPCOUNT],
             width: MAPWIDTH as i32,
             height: MAPHEIGHT as i32,
             revealed_tiles: HashSet::new(),
@@ -209,6 +412,18 @@ impl Map {
     }
 }
 
+impl Map {
+    // dropping further than we can climb hurts, so path around it if possible
+    fn exit_cost(&self, from_idx: usize, to_idx: usize) -> f32 {
+        let drop = self.heights[from_idx] - self.heights[to_idx];
+        if drop > MAX_CLIMB {
+            1.0 + drop as f32
+        } else {
+            1.0
+        }
+    }
+}
+
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
         self.tiles[idx] == TileType::Wall
@@ -220,30 +435,30 @@ impl BaseMap for Map {
         let y = idx as i32 / self.width;
         let w = self.width as usize;
 
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0));
+        if self.is_exit_valid(idx, x - 1, y) {
+            exits.push((idx - 1, self.exit_cost(idx, idx - 1)));
         }
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0));
+        if self.is_exit_valid(idx, x + 1, y) {
+            exits.push((idx + 1, self.exit_cost(idx, idx + 1)));
         }
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0));
+        if self.is_exit_valid(idx, x, y - 1) {
+            exits.push((idx - w, self.exit_cost(idx, idx - w)));
         }
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0));
+        if self.is_exit_valid(idx, x, y + 1) {
+            exits.push((idx + w, self.exit_cost(idx, idx + w)));
         }
 
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.0));
+        if self.is_exit_valid(idx, x - 1, y - 1) {
+            exits.push(((idx - w) - 1, self.exit_cost(idx, (idx - w) - 1)));
         }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.0));
+        if self.is_exit_valid(idx, x + 1, y - 1) {
+            exits.push(((idx - w) + 1, self.exit_cost(idx, (idx - w) + 1)));
         }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.0));
+        if self.is_exit_valid(idx, x - 1, y + 1) {
+            exits.push(((idx + w) - 1, self.exit_cost(idx, (idx + w) - 1)));
         }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.0));
+        if self.is_exit_valid(idx, x + 1, y + 1) {
+            exits.push(((idx + w) + 1, self.exit_cost(idx, (idx + w) + 1)));
         }
 
         exits
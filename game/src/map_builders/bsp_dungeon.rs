@@ -1,31 +1,24 @@
-use rltk::{Point, RandomNumberGenerator, Rect};
+use rltk::{RandomNumberGenerator, Rect};
 
-use crate::{
-    map::{Map, TileType, MAPHEIGHT, MAPWIDTH},
-    spawner, SHOW_MAPGEN_VISUALIZER,
-};
+use crate::map::{Map, TileType};
 
-use super::{common::apply_room_to_map, MapBuilder};
+use super::{common::{apply_room_to_map, draw_corridor}, BuilderMap, MapBuilder};
 
+#[derive(Default)]
 pub struct BspDungeonBuilder {
-    map: Map,
-    starting_position: Point,
-    depth: i32,
-    rooms: Vec<Rect>,
-    history: Vec<Map>,
     rects: Vec<Rect>,
 }
 
 impl MapBuilder for BspDungeonBuilder {
-    fn build_map(&mut self, ecs: &mut specs::World) {
-        let mut rng = ecs.fetch_mut::<RandomNumberGenerator>();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let mut rooms: Vec<Rect> = Vec::new();
 
         self.rects.clear();
         self.rects.push(Rect {
             x1: 2,
-            x2: self.map.width - 5,
+            x2: build_data.map.width - 5,
             y1: 2,
-            y2: self.map.height - 5,
+            y2: build_data.map.height - 5,
         });
         let first_room = self.rects[0];
 
@@ -34,81 +27,42 @@ impl MapBuilder for BspDungeonBuilder {
 
         let mut n_rooms = 0;
         while n_rooms < 240 {
-            let rect = self.get_random_rect(&mut rng);
-            let candidate = self.get_random_subrect(rect, &mut rng);
+            let rect = self.get_random_rect(rng);
+            let candidate = self.get_random_subrect(rect, rng);
 
-            if self.is_possible(candidate) {
-                apply_room_to_map(&mut self.map, &candidate);
-                self.rooms.push(candidate);
+            if self.is_possible(&build_data.map, candidate) {
+                apply_room_to_map(&mut build_data.map, &candidate);
+                rooms.push(candidate);
                 self.add_subrects(rect);
-                self.take_snapshot();
+                build_data.take_snapshot();
             }
 
             n_rooms += 1;
         }
 
-        self.starting_position = self.rooms[0].center();
-
-        self.rooms.sort_by(|a, b| {
+        rooms.sort_by(|a, b| {
             a.x1.cmp(&b.x1)
         });
 
-        for i in 0..self.rooms.len()-1 {
-            let room = self.rooms[i];
-            let next_room = self.rooms[i + 1];
+        for i in 0..rooms.len()-1 {
+            let room = rooms[i];
+            let next_room = rooms[i + 1];
 
             let start_x = room.x1 + rng.roll_dice(1, i32::abs(room.x1 - room.x2)) - 1;
             let start_y = room.y1 + rng.roll_dice(1, i32::abs(room.y1 - room.y2)) - 1;
             let end_x = next_room.x1 + rng.roll_dice(1, i32::abs(next_room.x1 - next_room.x2)) - 1;
             let end_y = next_room.y1 + rng.roll_dice(1, i32::abs(next_room.y1 - next_room.y2)) - 1;
-            self.draw_corridor(start_x, start_y, end_x, end_y);
-            self.take_snapshot();
-        }
-
-        let stairs_position = self.rooms[self.rooms.len() - 1].center();
-        let stairs_idx = self.map.xy_flat(stairs_position.x, stairs_position.y);
-        self.map.tiles[stairs_idx] = TileType::DownStairs;
-    }
-
-    fn spawn_entities(&mut self, ecs: &mut specs::World) {
-        for room in self.rooms.iter().skip(1) {
-            spawner::spawn_room(ecs, room);
+            draw_corridor(&mut build_data.map, start_x, start_y, end_x, end_y);
+            build_data.take_snapshot();
         }
-    }
-
-    fn get_map(&self) -> Map {
-        self.map.clone()
-    }
-
-    fn get_starting_position(&self) -> Point {
-        self.starting_position.clone()
-    }
-
-    fn get_snapshot_history(&self) -> Vec<Map> {
-        self.history.clone()
-    }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for y in 0..MAPHEIGHT {
-                for x in 0..MAPWIDTH {
-                    snapshot.revealed_tiles.insert(Point::new(x, y));
-                }
-            }
-            self.history.push(snapshot);
-        }
+        build_data.rooms = Some(rooms);
     }
 }
 
 impl BspDungeonBuilder {
-    pub fn new(new_depth: i32) -> Self {
+    pub fn new() -> Self {
         BspDungeonBuilder {
-            map: Map::new(new_depth),
-            starting_position: Point::new(0, 0),
-            depth: new_depth,
-            rooms: Vec::new(),
-            history: Vec::new(),
             rects: Vec::new(),
         }
     }
@@ -157,8 +111,9 @@ impl BspDungeonBuilder {
 
     fn get_random_subrect(&self, rect: Rect, rng: &mut RandomNumberGenerator) -> Rect {
         let mut result = rect;
-        let rect_width = i32::abs(rect.x1 - rect.x2);
-        let rect_height = i32::abs(rect.y1 - rect.y2);
+        // degenerate rects would ask for a zero sided die
+        let rect_width = i32::max(1, i32::abs(rect.x1 - rect.x2));
+        let rect_height = i32::max(1, i32::abs(rect.y1 - rect.y2));
 
         let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
         let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
@@ -171,7 +126,7 @@ impl BspDungeonBuilder {
         result
     }
 
-    fn is_possible(&self, rect: Rect) -> bool {
+    fn is_possible(&self, map: &Map, rect: Rect) -> bool {
         let mut expanded = rect;
         expanded.x1 -= 2;
         expanded.x2 += 2;
@@ -182,13 +137,13 @@ impl BspDungeonBuilder {
 
         for y in expanded.y1..expanded.y2 {
             for x in expanded.x1..expanded.x2 {
-                if x > self.map.width - 2 || y > self.map.height - 2 || x < 1 || y < 1 {
+                if x > map.width - 2 || y > map.height - 2 || x < 1 || y < 1 {
                     can_build = false;
                 }
 
                 if can_build {
-                    let idx = self.map.xy_flat(x, y);
-                    if self.map.tiles[idx] != TileType::Wall {
+                    let idx = map.xy_flat(x, y);
+                    if map.tiles[idx] != TileType::Wall {
                         can_build = false;
                     }
                 }
@@ -197,27 +152,4 @@ impl BspDungeonBuilder {
 
         can_build
     }
-
-    fn draw_corridor(&mut self, start_x: i32, start_y: i32, end_x: i32, end_y: i32) {
-        let mut x = start_x;
-        let mut y = start_y;
-
-        while x != end_x || y != end_y {
-            if x < end_x {
-                x += 1;
-            }
-            else if x > end_x {
-                x -= 1;
-            }
-            else if y < end_y {
-                y += 1;
-            }
-            else if y > end_y {
-                y -= 1;
-            }
-
-            let idx = self.map.xy_flat(x, y);
-            self.map.tiles[idx] = TileType::Floor;
-        }
-    }
 }
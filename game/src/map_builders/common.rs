@@ -1,6 +1,15 @@
-use rltk::{Point, Rect};
+use std::collections::BTreeMap;
 
-use crate::map::{Map, TileType, MAPHEIGHT, MAPWIDTH};
+use rltk::{Point, RandomNumberGenerator, Rect};
+
+use crate::map::{Map, Surface, TileType, MAPHEIGHT, MAPWIDTH, MAX_CLIMB, MAX_HEIGHT};
+
+const MAX_PLATFORMS: i32 = 6;
+const MAX_TERRAIN_TRIES: i32 = 10;
+// fraction of the floor that has to stay reachable once the terrain is raised
+const MIN_TERRAIN_REACHABLE: f32 = 0.85;
+// nothing spawns closer than this to where the player starts
+const MIN_SPAWN_DISTANCE: f32 = 6.0;
 
 pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
     for y in room.y1 + 1..room.y2 {
@@ -28,3 +37,197 @@ pub fn apply_tunnel(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) {
     }
 }
 
+
+// digs an L shaped corridor, moving along x first
+pub fn draw_corridor(map: &mut Map, start_x: i32, start_y: i32, end_x: i32, end_y: i32) {
+    let mut x = start_x;
+    let mut y = start_y;
+
+    while x != end_x || y != end_y {
+        if x < end_x {
+            x += 1;
+        }
+        else if x > end_x {
+            x -= 1;
+        }
+        else if y < end_y {
+            y += 1;
+        }
+        else if y > end_y {
+            y -= 1;
+        }
+
+        let idx = map.xy_flat(x, y);
+        map.tiles[idx] = TileType::Floor;
+    }
+}
+
+// lays a straight (horizontal, vertical or diagonal) rail from (x1, y1) to (x2, y2),
+// only replacing floor tiles
+pub fn apply_rail(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) {
+    let xdir = (x2 - x1).signum();
+    let ydir = (y2 - y1).signum();
+
+    for point in rltk::line2d_bresenham(Point::new(x1, y1), Point::new(x2, y2)) {
+        if point.x < MAPWIDTH as i32 && point.y < MAPHEIGHT as i32 {
+            let idx = map.xy_flat(point.x, point.y);
+            if map.tiles[idx] == TileType::Floor {
+                map.tiles[idx] = TileType::Rail { xdir, ydir };
+                map.surfaces[idx] = Surface::Plain;
+            }
+        }
+    }
+}
+
+// raises terrain and platforms over the layout, retrying when the ledges would
+// cut too much of the level off from start_idx. Falls back to a flat map.
+pub fn apply_terrain(map: &mut Map, rng: &mut RandomNumberGenerator, start_idx: usize) {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let floor_count = map.tiles.iter().filter(|t| **t != TileType::Wall).count();
+    for _ in 0..MAX_TERRAIN_TRIES {
+        apply_heights(map, rng);
+        apply_platforms(map, rng);
+
+        let map_starts = vec![start_idx];
+        let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+        let reachable_count = map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| **t != TileType::Wall && dijkstra_map.map[*i] != f32::MAX)
+            .count();
+
+        if reachable_count as f32 >= floor_count as f32 * MIN_TERRAIN_REACHABLE {
+            return;
+        }
+    }
+
+    for height in map.heights.iter_mut() {
+        *height = 0;
+    }
+}
+
+// rolling terrain: quantized noise gives gentle slopes with the odd ledge
+pub fn apply_heights(map: &mut Map, rng: &mut RandomNumberGenerator) {
+    let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+    noise.set_noise_type(rltk::NoiseType::Simplex);
+    noise.set_frequency(0.08);
+
+    let terrain_height = MAX_HEIGHT / 2;
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let n = (noise.get_noise(x as f32, y as f32) + 1.0) / 2.0;
+            let idx = map.xy_flat(x, y);
+            map.heights[idx] = ((n * (terrain_height + 1) as f32) as i32).clamp(0, terrain_height);
+        }
+    }
+}
+
+// raised platforms whose sides are ledges too high to climb,
+// each with a ramp leading up onto it
+pub fn apply_platforms(map: &mut Map, rng: &mut RandomNumberGenerator) {
+    for _ in 0..MAX_PLATFORMS {
+        let w = rng.range(4, 10);
+        let h = rng.range(3, 7);
+        let x = rng.roll_dice(1, map.width - w - 3) + 1;
+        let y = rng.roll_dice(1, map.height - h - 3) + 1;
+        let platform = Rect::with_size(x, y, w, h);
+
+        let mut base = 0;
+        platform.for_each(|p| {
+            base = i32::max(base, map.heights[map.xy_flat(p.x, p.y)]);
+        });
+        let top = i32::min(base + MAX_CLIMB + 1, MAX_HEIGHT);
+        platform.for_each(|p| {
+            let idx = map.xy_flat(p.x, p.y);
+            map.heights[idx] = top;
+        });
+
+        // walk outwards from the middle of one side, stepping down
+        // until we meet the surrounding terrain
+        let (mut ramp, dir) = match rng.roll_dice(1, 4) {
+            1 => (Point::new(platform.x1 - 1, platform.center().y), Point::new(-1, 0)),
+            2 => (Point::new(platform.x2, platform.center().y), Point::new(1, 0)),
+            3 => (Point::new(platform.center().x, platform.y1 - 1), Point::new(0, -1)),
+            _ => (Point::new(platform.center().x, platform.y2), Point::new(0, 1)),
+        };
+        let mut step_height = top;
+        while ramp.x > 0 && ramp.x < map.width - 1 && ramp.y > 0 && ramp.y < map.height - 1 {
+            let idx = map.xy_flat(ramp.x, ramp.y);
+            if map.heights[idx] >= step_height - MAX_CLIMB {
+                break;
+            }
+            map.heights[idx] = step_height - MAX_CLIMB;
+            step_height = map.heights[idx];
+            ramp += dir;
+        }
+    }
+}
+
+// turns every floor tile that can't be reached from start_idx into wall,
+// handing back how far every tile is from it
+pub fn cull_unreachable(map: &mut Map, start_idx: usize) -> Vec<f32> {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let map_starts = vec![start_idx];
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+
+    for (i, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor && dijkstra_map.map[i] == f32::MAX {
+            *tile = TileType::Wall;
+        }
+    }
+
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    dijkstra_map.map
+}
+
+// the index of the reachable floor tile furthest from start_idx
+pub fn farthest_reachable(map: &mut Map, start_idx: usize) -> usize {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let map_starts = vec![start_idx];
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+    let mut exit_tile = (start_idx, 0.0f32);
+
+    for (i, tile) in map.tiles.iter().enumerate() {
+        let dist_to_start = dijkstra_map.map[i];
+        if *tile == TileType::Floor && dist_to_start != f32::MAX && dist_to_start > exit_tile.1 {
+            exit_tile.0 = i;
+            exit_tile.1 = dist_to_start;
+        }
+    }
+
+    exit_tile.0
+}
+
+// splits the reachable floor into areas using cellular noise, so each area can
+// be populated like a room. Keyed by a BTreeMap so the order is the same for a seed.
+pub fn generate_spawn_regions(map: &Map, rng: &mut RandomNumberGenerator, distances: &[f32]) -> BTreeMap<i32, Vec<usize>> {
+    let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    let mut regions: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let idx = map.xy_flat(x, y);
+            let dist_to_start = distances[idx];
+            if map.tiles[idx] != TileType::Floor || dist_to_start == f32::MAX || dist_to_start < MIN_SPAWN_DISTANCE {
+                continue;
+            }
+
+            let cell_value = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+            regions.entry(cell_value).or_default().push(idx);
+        }
+    }
+
+    regions
+}
@@ -0,0 +1,21 @@
+use rltk::RandomNumberGenerator;
+
+use super::{common::cull_unreachable, BuilderMap, MetaMapBuilder};
+
+// walls off every floor tile the player can't walk to from the start
+#[derive(Default)]
+pub struct CullUnreachable {}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        build_data.distances = Some(cull_unreachable(&mut build_data.map, start_idx));
+        build_data.take_snapshot();
+    }
+}
+
+impl CullUnreachable {
+    pub fn new() -> CullUnreachable {
+        CullUnreachable {}
+    }
+}
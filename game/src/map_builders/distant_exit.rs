@@ -0,0 +1,31 @@
+use rltk::RandomNumberGenerator;
+
+use crate::map::TileType;
+
+use super::{common::farthest_reachable, BuilderMap, MetaMapBuilder};
+
+// puts the down stairs on the reachable tile furthest from the start,
+// replacing any stairs placed before
+#[derive(Default)]
+pub struct DistantExit {}
+
+impl MetaMapBuilder for DistantExit {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        for tile in build_data.map.tiles.iter_mut() {
+            if *tile == TileType::DownStairs {
+                *tile = TileType::Floor;
+            }
+        }
+
+        let start_idx = build_data.starting_idx();
+        let exit_idx = farthest_reachable(&mut build_data.map, start_idx);
+        build_data.map.tiles[exit_idx] = TileType::DownStairs;
+        build_data.take_snapshot();
+    }
+}
+
+impl DistantExit {
+    pub fn new() -> DistantExit {
+        DistantExit {}
+    }
+}
@@ -0,0 +1,168 @@
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::map::{Map, TileType};
+
+use super::{mirror::Symmetry, BuilderMap, MapBuilder};
+
+// the visualizer only gets a frame every few diggers
+const DIGGERS_PER_SNAPSHOT: i32 = 20;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum DLAAlgorithm {
+    WalkInwards,
+    WalkOutwards,
+    CentralAttractor,
+}
+
+// diffusion-limited aggregation: diggers wander until they bump into the
+// floor grown so far, and stick where they hit it
+pub struct DLABuilder {
+    algorithm: DLAAlgorithm,
+    brush_size: i32,
+    symmetry: Option<Symmetry>,
+    floor_percent: f32,
+}
+
+impl MapBuilder for DLABuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // seed the growth with a small cross in the middle
+        let center = Point::new(build_data.map.width / 2, build_data.map.height / 2);
+        for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let idx = build_data.map.xy_flat(center.x + dx, center.y + dy);
+            build_data.map.tiles[idx] = TileType::Floor;
+        }
+        build_data.take_snapshot();
+
+        let total_tiles = build_data.map.width * build_data.map.height;
+        let desired_floor_tiles = (self.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = 5;
+        let mut digger_count = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            let map = &mut build_data.map;
+            match self.algorithm {
+                DLAAlgorithm::WalkInwards => {
+                    let mut digger = random_point(map, rng);
+                    let mut prev = digger;
+                    while map.tiles[map.xy_flat(digger.x, digger.y)] == TileType::Wall {
+                        prev = digger;
+                        stagger(map, rng, &mut digger);
+                    }
+                    self.paint(map, prev.x, prev.y);
+                }
+                DLAAlgorithm::WalkOutwards => {
+                    let mut digger = center;
+                    while map.tiles[map.xy_flat(digger.x, digger.y)] != TileType::Wall {
+                        stagger(map, rng, &mut digger);
+                    }
+                    self.paint(map, digger.x, digger.y);
+                }
+                DLAAlgorithm::CentralAttractor => {
+                    let start = random_point(map, rng);
+                    let mut prev = start;
+                    for digger in rltk::line2d_bresenham(start, center) {
+                        if map.tiles[map.xy_flat(digger.x, digger.y)] != TileType::Wall {
+                            break;
+                        }
+                        prev = digger;
+                    }
+                    self.paint(map, prev.x, prev.y);
+                }
+            }
+
+            floor_tile_count = map.tiles.iter().filter(|t| **t == TileType::Floor).count();
+            digger_count += 1;
+            if digger_count % DIGGERS_PER_SNAPSHOT == 0 {
+                build_data.take_snapshot();
+            }
+        }
+        build_data.take_snapshot();
+    }
+}
+
+impl DLABuilder {
+    pub fn new(
+        algorithm: DLAAlgorithm,
+        brush_size: i32,
+        symmetry: Option<Symmetry>,
+        floor_percent: f32,
+    ) -> DLABuilder {
+        DLABuilder {
+            algorithm,
+            brush_size,
+            symmetry,
+            floor_percent,
+        }
+    }
+
+    // a coral-like growth reaching in from the edges
+    pub fn walk_inwards() -> DLABuilder {
+        DLABuilder::new(DLAAlgorithm::WalkInwards, 1, None, 0.25)
+    }
+
+    // wide tunnels pushed out from the middle
+    pub fn walk_outwards() -> DLABuilder {
+        DLABuilder::new(DLAAlgorithm::WalkOutwards, 2, None, 0.25)
+    }
+
+    // spokes drawn straight towards the middle
+    pub fn central_attractor() -> DLABuilder {
+        DLABuilder::new(DLAAlgorithm::CentralAttractor, 2, None, 0.25)
+    }
+
+    // a mirrored bug shape
+    pub fn insectoid() -> DLABuilder {
+        DLABuilder::new(
+            DLAAlgorithm::CentralAttractor,
+            2,
+            Some(Symmetry::Horizontal),
+            0.25,
+        )
+    }
+
+    fn paint(&self, map: &mut Map, x: i32, y: i32) {
+        let center_x = map.width / 2;
+        let center_y = map.height / 2;
+        let (mirror_x, mirror_y) = (center_x * 2 - x, center_y * 2 - y);
+
+        self.apply_paint(map, x, y);
+        match self.symmetry {
+            None => {}
+            Some(Symmetry::Horizontal) => self.apply_paint(map, mirror_x, y),
+            Some(Symmetry::Vertical) => self.apply_paint(map, x, mirror_y),
+            Some(Symmetry::Both) => {
+                self.apply_paint(map, mirror_x, y);
+                self.apply_paint(map, x, mirror_y);
+                self.apply_paint(map, mirror_x, mirror_y);
+            }
+        }
+    }
+
+    fn apply_paint(&self, map: &mut Map, x: i32, y: i32) {
+        for by in y..y + self.brush_size {
+            for bx in x..x + self.brush_size {
+                if bx > 1 && bx < map.width - 2 && by > 1 && by < map.height - 2 {
+                    let idx = map.xy_flat(bx, by);
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+    }
+}
+
+fn random_point(map: &Map, rng: &mut RandomNumberGenerator) -> Point {
+    Point::new(
+        rng.roll_dice(1, map.width - 3) + 1,
+        rng.roll_dice(1, map.height - 3) + 1,
+    )
+}
+
+// one random step, staying off the edge of the map
+fn stagger(map: &Map, rng: &mut RandomNumberGenerator, digger: &mut Point) {
+    match rng.roll_dice(1, 4) {
+        1 => digger.x = i32::max(2, digger.x - 1),
+        2 => digger.x = i32::min(map.width - 3, digger.x + 1),
+        3 => digger.y = i32::max(2, digger.y - 1),
+        _ => digger.y = i32::min(map.height - 3, digger.y + 1),
+    }
+}
@@ -0,0 +1,110 @@
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::map::TileType;
+
+use super::{BuilderMap, MapBuilder};
+
+// the visualizer only gets a frame every few diggers
+const DIGGERS_PER_SNAPSHOT: i32 = 10;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum DrunkSpawnMode {
+    StartingPoint,
+    Random,
+}
+
+pub struct DrunkardSettings {
+    pub spawn_mode: DrunkSpawnMode,
+    pub drunken_lifetime: i32,
+    pub floor_percent: f32,
+}
+
+// sends diggers stumbling around until enough of the map is floor
+pub struct DrunkardsWalkBuilder {
+    settings: DrunkardSettings,
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let center = Point::new(build_data.map.width / 2, build_data.map.height / 2);
+        let center_idx = build_data.map.xy_flat(center.x, center.y);
+        build_data.map.tiles[center_idx] = TileType::Floor;
+
+        let total_tiles = build_data.map.width * build_data.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = 1;
+        let mut digger_count = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            let mut drunk = match self.settings.spawn_mode {
+                DrunkSpawnMode::StartingPoint => center,
+                DrunkSpawnMode::Random => {
+                    if digger_count == 0 {
+                        center
+                    } else {
+                        Point::new(
+                            rng.roll_dice(1, build_data.map.width - 3) + 1,
+                            rng.roll_dice(1, build_data.map.height - 3) + 1,
+                        )
+                    }
+                }
+            };
+
+            let mut life = self.settings.drunken_lifetime;
+            while life > 0 {
+                let idx = build_data.map.xy_flat(drunk.x, drunk.y);
+                if build_data.map.tiles[idx] == TileType::Wall {
+                    build_data.map.tiles[idx] = TileType::Floor;
+                    floor_tile_count += 1;
+                }
+
+                match rng.roll_dice(1, 4) {
+                    1 => drunk.x = i32::max(2, drunk.x - 1),
+                    2 => drunk.x = i32::min(build_data.map.width - 3, drunk.x + 1),
+                    3 => drunk.y = i32::max(2, drunk.y - 1),
+                    _ => drunk.y = i32::min(build_data.map.height - 3, drunk.y + 1),
+                }
+                life -= 1;
+            }
+
+            digger_count += 1;
+            if digger_count % DIGGERS_PER_SNAPSHOT == 0 {
+                build_data.take_snapshot();
+            }
+        }
+        build_data.take_snapshot();
+    }
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(settings: DrunkardSettings) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder { settings }
+    }
+
+    // a big blob around the middle
+    pub fn open_area() -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::StartingPoint,
+            drunken_lifetime: 400,
+            floor_percent: 0.5,
+        })
+    }
+
+    // clearings dotted around the map
+    pub fn open_halls() -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 400,
+            floor_percent: 0.5,
+        })
+    }
+
+    // lots of short lived diggers leave narrow alleys
+    pub fn winding_passages() -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 100,
+            floor_percent: 0.4,
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use rltk::RandomNumberGenerator;
+
+use crate::map::TileType;
+
+use super::{BuilderMap, MetaMapBuilder};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Symmetry {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+// copies the left and/or top half of the map over the other half
+pub struct MirrorBuilder {
+    symmetry: Symmetry,
+}
+
+impl MetaMapBuilder for MirrorBuilder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let (horizontal, vertical) = match self.symmetry {
+            Symmetry::Horizontal => (true, false),
+            Symmetry::Vertical => (false, true),
+            Symmetry::Both => (true, true),
+        };
+
+        let map = &mut build_data.map;
+        if horizontal {
+            for y in 0..map.height {
+                for x in 0..map.width / 2 {
+                    let from = map.xy_flat(x, y);
+                    let to = map.xy_flat(map.width - 1 - x, y);
+                    map.tiles[to] = mirror_tile(map.tiles[from], -1, 1);
+                    map.heights[to] = map.heights[from];
+                }
+            }
+        }
+        if vertical {
+            for y in 0..map.height / 2 {
+                for x in 0..map.width {
+                    let from = map.xy_flat(x, y);
+                    let to = map.xy_flat(x, map.height - 1 - y);
+                    map.tiles[to] = mirror_tile(map.tiles[from], 1, -1);
+                    map.heights[to] = map.heights[from];
+                }
+            }
+        }
+
+        build_data.take_snapshot();
+    }
+}
+
+impl MirrorBuilder {
+    pub fn new(symmetry: Symmetry) -> MirrorBuilder {
+        MirrorBuilder { symmetry }
+    }
+}
+
+// rails have a direction, so they get flipped along with the map
+fn mirror_tile(tile: TileType, xflip: i32, yflip: i32) -> TileType {
+    match tile {
+        TileType::Rail { xdir, ydir } => TileType::Rail {
+            xdir: xdir * xflip,
+            ydir: ydir * yflip,
+        },
+        _ => tile,
+    }
+}
@@ -3,21 +3,309 @@ pub mod common;
 pub mod simple_map;
 pub mod bsp_interior_builder;
 pub mod cellular_automata_builder;
+pub mod cull_unreachable;
+pub mod distant_exit;
+pub mod dla;
+pub mod drunkard;
+pub mod mirror;
+pub mod momentum_validator;
+pub mod prefab_builder;
+pub mod rail_builder;
+pub mod rail_validator;
+pub mod spawn_regions;
+pub mod starting_position;
+pub mod surfaces;
+pub mod terrain;
+pub mod waveform_collapse;
 
-use rltk::Point;
+use rltk::{console, Point, RandomNumberGenerator, Rect};
 use specs::{World};
 
 use super::Map;
+use crate::{map::{TileType, MAPHEIGHT, MAPWIDTH}, physics_config::Physics, prefabs::PrefabMaster, random_table::RandomTable, spawner, SHOW_MAPGEN_VISUALIZER};
 
+use self::{
+    bsp_dungeon::BspDungeonBuilder, bsp_interior_builder::BspInteriorBuilder,
+    cellular_automata_builder::CellularAutomataBuilder, cull_unreachable::CullUnreachable,
+    distant_exit::DistantExit, dla::DLABuilder, drunkard::DrunkardsWalkBuilder, mirror::{MirrorBuilder, Symmetry},
+    momentum_validator::validate_reachability, prefab_builder::{PrefabBuilder, PrefabVaults}, rail_builder::RailBuilder,
+    rail_validator::{has_rail_shortcut, SHORTCUT_DEPTH},
+    simple_map::SimpleMapBuilder, spawn_regions::{NoiseRegionSpawner, RoomSpawner},
+    starting_position::{CenterStartingPosition, RoomBasedStartingPosition}, surfaces::SurfaceBuilder, terrain::TerrainBuilder,
+    waveform_collapse::WaveformCollapseBuilder,
+};
+
+// a level whose stairs can't be reached without falling over, or deep enough
+// to need a rail shortcut and missing one, is built again up to this many
+// times before we fall back to plain rooms
+const MAX_LEVEL_TRIES: i32 = 10;
+
+// everything a chain of builders works on and hands along
+pub struct BuilderMap {
+    pub map: Map,
+    pub starting_position: Option<Point>,
+    pub rooms: Option<Vec<Rect>>,
+    // areas (as map indices) that get populated like a room each
+    pub spawn_regions: Vec<Vec<usize>>,
+    // things placed by name, like the contents of a vault
+    pub spawn_list: Vec<(usize, String)>,
+    // walking distance of every tile from the start, worked out when the
+    // unreachable parts are culled
+    pub distances: Option<Vec<f32>>,
+    pub history: Vec<Map>,
+}
+
+impl BuilderMap {
+    pub fn new(new_depth: i32) -> BuilderMap {
+        BuilderMap {
+            map: Map::new(new_depth),
+            starting_position: None,
+            rooms: None,
+            spawn_regions: Vec::new(),
+            spawn_list: Vec::new(),
+            distances: None,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn take_snapshot(&mut self) {
+        if SHOW_MAPGEN_VISUALIZER {
+            let mut snapshot = self.map.clone();
+            for y in 0..MAPHEIGHT {
+                for x in 0..MAPWIDTH {
+                    snapshot.revealed_tiles.insert(Point::new(x, y));
+                }
+            }
+            self.history.push(snapshot);
+        }
+    }
+
+    pub fn starting_idx(&self) -> usize {
+        let start = self.starting_position.expect("Unable to find a starting position, add one to the chain first");
+        self.map.xy_flat(start.x, start.y)
+    }
+}
+
+// makes the initial layout of a level
 pub trait MapBuilder {
-    fn build_map(&mut self, ecs: &mut World);
-    fn spawn_entities(&mut self, ecs: &mut World);
-    fn get_map(&self) -> Map;
-    fn get_starting_position(&self) -> Point;
-    fn get_snapshot_history(&self) -> Vec<Map>;
-    fn take_snapshot(&mut self);
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+// transforms a layout made by the builders before it
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
 }
 
-pub fn random_builder(new_depth: i32) -> Box<dyn MapBuilder> {
-    Box::new(cellular_automata_builder::CellularAutomataBuilder::new(new_depth))
+pub struct BuilderChain {
+    starter: Option<Box<dyn MapBuilder>>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+    pub build_data: BuilderMap,
+}
+
+impl BuilderChain {
+    pub fn new(new_depth: i32) -> BuilderChain {
+        BuilderChain {
+            starter: None,
+            builders: Vec::new(),
+            build_data: BuilderMap::new(new_depth),
+        }
+    }
+
+    pub fn start_with(mut self, starter: Box<dyn MapBuilder>) -> BuilderChain {
+        if self.starter.is_some() {
+            panic!("A builder chain can only have one starting builder");
+        }
+        self.starter = Some(starter);
+        self
+    }
+
+    pub fn with(mut self, metabuilder: Box<dyn MetaMapBuilder>) -> BuilderChain {
+        self.builders.push(metabuilder);
+        self
+    }
+
+    pub fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        match &mut self.starter {
+            None => panic!("Unable to build a map without a starting builder"),
+            Some(starter) => starter.build_map(rng, &mut self.build_data),
+        }
+
+        for metabuilder in self.builders.iter_mut() {
+            metabuilder.build_map(rng, &mut self.build_data);
+        }
+    }
+
+    pub fn spawn_entities(&self, ecs: &mut World) {
+        for region in self.build_data.spawn_regions.iter() {
+            spawner::spawn_region(ecs, region);
+        }
+
+        let map = &self.build_data.map;
+        for (idx, name) in self.build_data.spawn_list.iter() {
+            // later stages may have walled it in
+            if map.tiles[*idx] != TileType::Wall {
+                spawner::spawn_named(ecs, name, *idx as i32 % map.width, *idx as i32 / map.width);
+            }
+        }
+    }
+
+    pub fn get_map(&self) -> Map {
+        self.build_data.map.clone()
+    }
+
+    pub fn get_starting_position(&self) -> Point {
+        self.build_data.starting_position.expect("Unable to find a starting position")
+    }
+
+    pub fn get_snapshot_history(&self) -> Vec<Map> {
+        self.build_data.history.clone()
+    }
+}
+
+// room layouts are most common near the surface, caves take over further down.
+// every generated chain ends the same way so each level has a start, a reachable exit,
+// rails, surfaces underfoot and something in it
+pub fn random_builder(new_depth: i32, rng: &mut RandomNumberGenerator, prefabs: &PrefabMaster, physics: &Physics) -> BuilderChain {
+    let levels = prefabs.level_table(new_depth);
+    let builders = RandomTable::new()
+        .add("Simple Map", i32::max(1, 6 - new_depth))
+        .add("BSP Dungeon", 4)
+        .add("BSP Interior", 3)
+        .add("Cellular Automata", 1 + new_depth)
+        .add("Drunkard's Walk", 3)
+        .add("Diffusion-Limited Aggregation", 2)
+        .add("Wave Function Collapse", 2)
+        .add("Prefab Level", levels.total_weight());
+
+    let builder = builders.roll(rng);
+
+    // hand-drawn levels come as they are
+    if builder.as_deref() == Some("Prefab Level") {
+        let name = levels.roll(rng).unwrap();
+        let prefab = prefabs.prefab(&name).unwrap().clone();
+        return BuilderChain::new(new_depth)
+            .start_with(Box::new(PrefabBuilder::new(prefab)))
+            .with(Box::new(CullUnreachable::new()));
+    }
+
+    let mut chain = BuilderChain::new(new_depth);
+    let room_based = match builder.as_deref() {
+        Some("Simple Map") => { chain = chain.start_with(Box::new(SimpleMapBuilder::new())); true }
+        Some("BSP Dungeon") => { chain = chain.start_with(Box::new(BspDungeonBuilder::new())); true }
+        Some("BSP Interior") => { chain = chain.start_with(Box::new(BspInteriorBuilder::new())); true }
+        Some("Drunkard's Walk") => {
+            let drunkard = match rng.roll_dice(1, 3) {
+                1 => DrunkardsWalkBuilder::open_area(),
+                2 => DrunkardsWalkBuilder::open_halls(),
+                _ => DrunkardsWalkBuilder::winding_passages(),
+            };
+            chain = chain.start_with(Box::new(drunkard));
+            false
+        }
+        Some("Diffusion-Limited Aggregation") => {
+            let dla = match rng.roll_dice(1, 4) {
+                1 => DLABuilder::walk_inwards(),
+                2 => DLABuilder::walk_outwards(),
+                3 => DLABuilder::central_attractor(),
+                _ => DLABuilder::insectoid(),
+            };
+            chain = chain.start_with(Box::new(dla));
+            false
+        }
+        Some("Wave Function Collapse") => {
+            // relearn the look of another builder, or of a hand-drawn level
+            let wfc = match (rng.roll_dice(1, 4), levels.roll(rng)) {
+                (1, _) => WaveformCollapseBuilder::from_builder(Box::new(DrunkardsWalkBuilder::winding_passages())),
+                (2, _) => WaveformCollapseBuilder::from_builder(Box::new(DLABuilder::walk_outwards())),
+                (3, Some(name)) => WaveformCollapseBuilder::from_prefab(prefabs.prefab(&name).unwrap().clone()),
+                _ => WaveformCollapseBuilder::from_builder(Box::new(CellularAutomataBuilder::new())),
+            };
+            chain = chain.start_with(Box::new(wfc));
+            false
+        }
+        _ => { chain = chain.start_with(Box::new(CellularAutomataBuilder::new())); false }
+    };
+
+    if room_based {
+        chain = chain.with(Box::new(RoomBasedStartingPosition::new()));
+    }
+    else {
+        // open layouts don't care about being cut in half, so they get mirrored now and then
+        match rng.roll_dice(1, 6) {
+            1 => chain = chain.with(Box::new(MirrorBuilder::new(Symmetry::Horizontal))),
+            2 => chain = chain.with(Box::new(MirrorBuilder::new(Symmetry::Vertical))),
+            3 => chain = chain.with(Box::new(MirrorBuilder::new(Symmetry::Both))),
+            _ => {}
+        }
+        chain = chain.with(Box::new(CenterStartingPosition::new()));
+    }
+
+    chain = chain
+        .with(Box::new(TerrainBuilder::new()))
+        .with(Box::new(PrefabVaults::new(prefabs.vault_table(new_depth), prefabs.prefabs.clone())))
+        .with(Box::new(CullUnreachable::new()))
+        .with(Box::new(DistantExit::new()))
+        .with(Box::new(SurfaceBuilder::new()))
+        .with(Box::new(RailBuilder::new(*physics)));
+
+    if room_based {
+        chain.with(Box::new(RoomSpawner::new()))
+    }
+    else {
+        chain.with(Box::new(NoiseRegionSpawner::new()))
+    }
+}
+
+// flat rooms joined by corridors, with nothing underfoot to slip on, can
+// always be walked. Only the rail shortcut can still go missing
+pub fn fallback_builder(new_depth: i32, physics: &Physics) -> BuilderChain {
+    BuilderChain::new(new_depth)
+        .start_with(Box::new(SimpleMapBuilder::new()))
+        .with(Box::new(RoomBasedStartingPosition::new()))
+        .with(Box::new(CullUnreachable::new()))
+        .with(Box::new(DistantExit::new()))
+        .with(Box::new(RailBuilder::new(*physics)))
+        .with(Box::new(RoomSpawner::new()))
+}
+
+// builds levels for the depth until one can be finished without falling over,
+// settling for the plain rooms fallback if none can
+pub fn build_level(new_depth: i32, rng: &mut RandomNumberGenerator, prefabs: &PrefabMaster, physics: &Physics) -> BuilderChain {
+    for tries in 1..=MAX_LEVEL_TRIES {
+        let mut builder = random_builder(new_depth, rng, prefabs, physics);
+        builder.build_map(rng);
+
+        if level_passes(&builder, new_depth, physics, tries) {
+            return builder;
+        }
+    }
+
+    // out of luck with the random layouts, settle for plain rooms as they come
+    let mut builder = fallback_builder(new_depth, physics);
+    builder.build_map(rng);
+    level_passes(&builder, new_depth, physics, MAX_LEVEL_TRIES + 1);
+    builder
+}
+
+// checks a finished level can be played and logs what the search found
+fn level_passes(builder: &BuilderChain, new_depth: i32, physics: &Physics, tries: i32) -> bool {
+    let mut map = builder.get_map();
+    let start = builder.get_starting_position();
+    let report = validate_reachability(&mut map, physics, start);
+    let start_idx = map.xy_flat(start.x, start.y);
+    let shortcut = new_depth < SHORTCUT_DEPTH || has_rail_shortcut(&mut map, physics, start_idx);
+
+    let verdict = if !report.stairs_reachable {
+        "rejected, the stairs can't be reached without falling"
+    } else if !shortcut {
+        "rejected, no rail cuts the way to the stairs short"
+    } else {
+        "accepted"
+    };
+    console::log(format!(
+        "Level {} at depth {} {}: walk {:?}, safe turns {:?}, {} states searched",
+        tries, new_depth, verdict, report.walk_distance, report.safe_turns, report.states_searched
+    ));
+
+    report.stairs_reachable && shortcut
 }
@@ -0,0 +1,199 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+use rltk::{Point, PointF};
+
+use crate::{
+    map::{Map, TileType},
+    movement_system::{MotionState, MovementSystem},
+    physics_config::Physics,
+};
+
+// velocity and lean are told apart to these fractions of a unit when
+// deciding whether we have been in a state before. Lean only ever gets as
+// far as fallover, so coarser steps keep the search quick
+const STATE_RESOLUTION: f32 = 2.0;
+const LEAN_RESOLUTION: f32 = 1.0;
+
+// what searching the level found
+#[derive(Clone, Copy, Debug)]
+pub struct ReachabilityReport {
+    pub stairs_reachable: bool,
+    // distance to the stairs over plain adjacency
+    pub walk_distance: Option<f32>,
+    // turns the quickest route that never falls over takes, slower than the
+    // walk whenever corners or ledges force us to ease off
+    pub safe_turns: Option<i32>,
+    pub states_searched: usize,
+}
+
+type StateKey = (Point, (i32, i32), (i32, i32), Option<Point>);
+
+fn state_key(state: &MotionState) -> StateKey {
+    let quantize = |v: PointF, resolution: f32| {
+        (
+            (v.x * resolution).round() as i32,
+            (v.y * resolution).round() as i32,
+        )
+    };
+    (
+        state.pos,
+        quantize(state.vel, STATE_RESOLUTION),
+        quantize(state.bal, LEAN_RESOLUTION),
+        state.grind,
+    )
+}
+
+// every push the player can make in a turn, and not pushing at all
+fn player_inputs(physics: &Physics) -> Vec<Option<PointF>> {
+    let mut inputs = vec![None];
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if dx != 0 || dy != 0 {
+                inputs.push(Some(
+                    PointF::new(dx as f32, dy as f32).normalized() * physics.inst,
+                ));
+            }
+        }
+    }
+    inputs
+}
+
+// searches every way the player can move from `start` under the real
+// velocity, balance and ledge rules, looking for a way onto the down stairs
+// that never falls over
+pub fn validate_reachability(map: &mut Map, physics: &Physics, start: Point) -> ReachabilityReport {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let mut report = ReachabilityReport {
+        stairs_reachable: false,
+        walk_distance: None,
+        safe_turns: None,
+        states_searched: 0,
+    };
+
+    let stairs = match map.tiles.iter().position(|t| *t == TileType::DownStairs) {
+        Some(idx) => Point::new(idx as i32 % map.width, idx as i32 / map.width),
+        None => return report,
+    };
+
+    let map_starts = vec![map.xy_flat(start.x, start.y)];
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+    let walk = dijkstra_map.map[map.xy_flat(stairs.x, stairs.y)];
+    if walk != f32::MAX {
+        report.walk_distance = Some(walk);
+    }
+
+    let inputs = player_inputs(physics);
+    let start_state = MotionState::at_rest(start);
+    let mut states = vec![start_state];
+    let mut seen = HashSet::new();
+    let mut open = BinaryHeap::new();
+    // lean has a direction, leaning further one way can still get us round
+    // a turn that leaning less the other way can't. So it's part of the
+    // state instead of something to prune on
+    seen.insert(state_key(&start_state));
+
+    // nothing covers more than max_speed tiles a turn, so this never
+    // overestimates the turns left and the first time we land on the stairs
+    // is about the quickest
+    let max_tiles = i32::max(physics.max_speed.round() as i32, 1);
+    let remaining = |pos: Point| {
+        let tiles = i32::max((stairs.x - pos.x).abs(), (stairs.y - pos.y).abs());
+        (tiles + max_tiles - 1) / max_tiles
+    };
+    open.push((Reverse(remaining(start)), 0, 0usize));
+
+    while let Some((_, turns, idx)) = open.pop() {
+        let state = states[idx];
+        report.states_searched += 1;
+        if state.pos == stairs {
+            report.stairs_reachable = true;
+            report.safe_turns = Some(turns);
+            return report;
+        }
+
+        for inst_vel in inputs.iter() {
+            if let Some(next) = MovementSystem::simulate_turn(map, physics, state, *inst_vel) {
+                if seen.insert(state_key(&next)) {
+                    open.push((
+                        Reverse(turns + 1 + remaining(next.pos)),
+                        turns + 1,
+                        states.len(),
+                    ));
+                    states.push(next);
+                }
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::MAX_CLIMB;
+
+    fn physics() -> Physics {
+        Physics {
+            inst: 0.77,
+            max_speed: 3.0,
+            speed_damp: 0.66,
+            zero_speed: 0.5,
+            balance_damp: 0.5,
+            lean_factor: 0.66,
+            fallover: 1.33,
+        }
+    }
+
+    fn carve(map: &mut Map, from: Point, to: Point) {
+        for p in rltk::line2d_bresenham(from, to) {
+            let idx = map.xy_flat(p.x, p.y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+
+    fn place_stairs(map: &mut Map, at: Point) {
+        let idx = map.xy_flat(at.x, at.y);
+        map.tiles[idx] = TileType::DownStairs;
+    }
+
+    #[test]
+    fn switchbacks_take_longer_than_the_walk() {
+        // four hairpin turns, each one forcing us to stop and go back
+        let mut map = Map::new(1);
+        let mut at = Point::new(2, 5);
+        for turn in 0..4 {
+            let x = if turn % 2 == 0 { 5 } else { 2 };
+            carve(&mut map, at, Point::new(x, at.y));
+            carve(&mut map, Point::new(x, at.y), Point::new(x, at.y + 2));
+            at = Point::new(x, at.y + 2);
+        }
+        place_stairs(&mut map, at);
+
+        let report = validate_reachability(&mut map, &physics(), Point::new(2, 5));
+        assert!(report.stairs_reachable);
+        let walk = report.walk_distance.unwrap();
+        let turns = report.safe_turns.unwrap();
+        assert!(turns as f32 > walk, "{} turns for a {} walk", turns, walk);
+    }
+
+    #[test]
+    fn a_ledge_in_the_way_is_rejected() {
+        // we could drop off the ledge on foot, but never without falling
+        let mut map = Map::new(1);
+        carve(&mut map, Point::new(2, 5), Point::new(20, 5));
+        for x in 2..=10 {
+            let idx = map.xy_flat(x, 5);
+            map.heights[idx] = MAX_CLIMB + 1;
+        }
+        place_stairs(&mut map, Point::new(20, 5));
+
+        let report = validate_reachability(&mut map, &physics(), Point::new(2, 5));
+        assert!(report.walk_distance.is_some());
+        assert!(!report.stairs_reachable);
+        assert_eq!(report.safe_turns, None);
+    }
+}
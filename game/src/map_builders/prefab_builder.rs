@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::{
+    map::{Map, TileType},
+    prefabs::RawPrefab,
+    random_table::RandomTable,
+};
+
+use super::{BuilderMap, MapBuilder, MetaMapBuilder};
+
+const MAX_VAULTS: i32 = 3;
+const MAX_SPOT_TRIES: i32 = 20;
+// a vault can't wall off more than this share of the reachable floor
+const MIN_VAULT_REACHABLE: f32 = 0.9;
+
+// lays out a whole level from a hand-drawn prefab
+pub struct PrefabBuilder {
+    prefab: RawPrefab,
+}
+
+impl MapBuilder for PrefabBuilder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let (spawns, start) = self.prefab.apply(&mut build_data.map, Point::new(0, 0), 0);
+        build_data.spawn_list.extend(spawns);
+        build_data.starting_position = start;
+        build_data.take_snapshot();
+    }
+}
+
+impl PrefabBuilder {
+    pub fn new(prefab: RawPrefab) -> PrefabBuilder {
+        PrefabBuilder { prefab }
+    }
+}
+
+// stamps a few vaults into open floor, never over the start and never
+// where they would cut the level in two
+pub struct PrefabVaults {
+    table: RandomTable,
+    vaults: Vec<RawPrefab>,
+}
+
+impl MetaMapBuilder for PrefabVaults {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        let mut used_tiles: HashSet<usize> = HashSet::new();
+        let mut used_vaults: HashSet<String> = HashSet::new();
+
+        let n_vaults = rng.roll_dice(1, MAX_VAULTS + 1) - 1;
+        for _ in 0..n_vaults {
+            let vault = match self.table.roll(rng) {
+                Some(name) => self.vaults.iter().find(|v| v.name == name).unwrap(),
+                None => break,
+            };
+            if !used_vaults.insert(vault.name.clone()) {
+                continue;
+            }
+
+            let map = &build_data.map;
+            let mut spots = Vec::new();
+            for y in 1..map.height - vault.height() {
+                for x in 1..map.width - vault.width() {
+                    let footprint = footprint(map, vault, Point::new(x, y));
+                    if footprint.iter().all(|idx| {
+                        map.tiles[*idx] == TileType::Floor
+                            && *idx != start_idx
+                            && !used_tiles.contains(idx)
+                    }) {
+                        spots.push(Point::new(x, y));
+                    }
+                }
+            }
+
+            // try spots in random order until one keeps the level in one piece
+            let before = reachable_count(&mut build_data.map, start_idx);
+            let mut tries = 0;
+            while !spots.is_empty() && tries < MAX_SPOT_TRIES {
+                tries += 1;
+                let spot = spots.remove(rng.roll_dice(1, spots.len() as i32) as usize - 1);
+                let footprint = footprint(&build_data.map, vault, spot);
+                let base = footprint
+                    .iter()
+                    .map(|idx| build_data.map.heights[*idx])
+                    .min()
+                    .unwrap_or(0);
+
+                let mut map = build_data.map.clone();
+                let (spawns, _) = vault.apply(&mut map, spot, base);
+                if (reachable_count(&mut map, start_idx) as f32)
+                    < before as f32 * MIN_VAULT_REACHABLE
+                {
+                    continue;
+                }
+
+                build_data.map = map;
+                build_data.spawn_list.extend(spawns);
+                used_tiles.extend(footprint);
+                build_data.take_snapshot();
+                break;
+            }
+        }
+    }
+}
+
+impl PrefabVaults {
+    // `vaults` are the candidates, `table` the odds of each at this depth
+    pub fn new(table: RandomTable, vaults: Vec<RawPrefab>) -> PrefabVaults {
+        PrefabVaults { table, vaults }
+    }
+}
+
+fn footprint(map: &Map, vault: &RawPrefab, origin: Point) -> Vec<usize> {
+    let mut tiles = Vec::new();
+    for y in origin.y..origin.y + vault.height() {
+        for x in origin.x..origin.x + vault.width() {
+            tiles.push(map.xy_flat(x, y));
+        }
+    }
+    tiles
+}
+
+fn reachable_count(map: &mut Map, start_idx: usize) -> usize {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let map_starts = vec![start_idx];
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+    dijkstra_map.map.iter().filter(|d| **d != f32::MAX).count()
+}
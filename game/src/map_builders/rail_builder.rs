@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::{
+    map::{Map, TileType, MAX_CLIMB},
+    physics_config::Physics,
+};
+
+use super::{
+    common::apply_rail,
+    rail_validator::{
+        coasting_distance, has_rail_shortcut, rail_rides, SHORTCUT_DEPTH, SHORTCUT_RANGE,
+    },
+    BuilderMap, MetaMapBuilder,
+};
+
+const MAX_RAILS: i32 = 6;
+const MIN_RAIL_LENGTH: i32 = 6;
+const MAX_SHORTCUT_TRIES: i32 = 20;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+// lays a rail network along long straight runs of floor. Later lines branch
+// off earlier ones, crossing them at junctions, and runs that cross a ledge
+// are evened out into ramps. Rides are played out with the player's physics
+pub struct RailBuilder {
+    physics: Physics,
+}
+
+impl MetaMapBuilder for RailBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.lay_network(rng, build_data);
+
+        if build_data.map.depth >= SHORTCUT_DEPTH {
+            self.lay_shortcut(rng, build_data);
+        }
+    }
+}
+
+impl RailBuilder {
+    pub fn new(physics: Physics) -> RailBuilder {
+        RailBuilder { physics }
+    }
+
+    fn lay_network(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        let mut n_rails = 0;
+        let mut tries = 0;
+
+        while n_rails < MAX_RAILS && tries < 100 {
+            tries += 1;
+
+            let map = &build_data.map;
+            let rails = map
+                .tiles
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, tile)| match tile {
+                    TileType::Rail { xdir, ydir } => Some((idx, Point::new(*xdir, *ydir))),
+                    _ => None,
+                })
+                .collect::<Vec<(usize, Point)>>();
+
+            // once there is a line, half the others branch off one
+            let (x, y, (xdir, ydir)) = if !rails.is_empty() && rng.roll_dice(1, 2) == 1 {
+                let (idx, own) = rails[(rng.roll_dice(1, rails.len() as i32) - 1) as usize];
+                let across = DIRECTIONS
+                    .iter()
+                    .filter(|(dx, dy)| {
+                        (*dx, *dy) != (own.x, own.y) && (*dx, *dy) != (-own.x, -own.y)
+                    })
+                    .collect::<Vec<_>>();
+                let dir = *across[(rng.roll_dice(1, across.len() as i32) - 1) as usize];
+                (idx as i32 % map.width, idx as i32 / map.width, dir)
+            } else {
+                let floors = map
+                    .tiles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile == TileType::Floor)
+                    .map(|(idx, _)| idx as i32)
+                    .collect::<Vec<i32>>();
+                if floors.is_empty() {
+                    return;
+                }
+                let idx = floors[(rng.roll_dice(1, floors.len() as i32) - 1) as usize];
+                (
+                    idx % map.width,
+                    idx / map.width,
+                    DIRECTIONS[(rng.roll_dice(1, 4) - 1) as usize],
+                )
+            };
+
+            let from = extend_run(map, x, y, -xdir, -ydir);
+            let to = extend_run(map, x, y, xdir, ydir);
+            if lay_run(&mut build_data.map, start_idx, from, to) {
+                build_data.take_snapshot();
+                n_rails += 1;
+            }
+        }
+    }
+
+    // tries rails that end right by the stairs until one can be ridden
+    // there from somewhere we can walk to
+    fn lay_shortcut(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        if has_rail_shortcut(&mut build_data.map, &self.physics, start_idx) {
+            return;
+        }
+
+        let map = &build_data.map;
+        let stairs = match map.tiles.iter().position(|t| *t == TileType::DownStairs) {
+            Some(idx) => Point::new(idx as i32 % map.width, idx as i32 / map.width),
+            None => return,
+        };
+
+        // every floor tile close to the stairs with a long enough run of
+        // floor leading up to it
+        let range = SHORTCUT_RANGE as i32;
+        let coast = coasting_distance(&self.physics);
+        let mut candidates = Vec::new();
+        for y in stairs.y - range..=stairs.y + range {
+            for x in stairs.x - range..=stairs.x + range {
+                if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 {
+                    continue;
+                }
+                let exit = Point::new(x, y);
+                if map.tiles[map.xy_flat(x, y)] != TileType::Floor
+                    || rltk::DistanceAlg::Pythagoras.distance2d(exit, stairs) > SHORTCUT_RANGE
+                {
+                    continue;
+                }
+                for (dx, dy) in DIRECTIONS.iter() {
+                    for dir in [Point::new(*dx, *dy), Point::new(-*dx, -*dy)] {
+                        // the rail runs up to the exit from as far back as the
+                        // floor goes, but no further than we can coast
+                        let from = extend_run(map, x, y, -dir.x, -dir.y);
+                        let length =
+                            i32::min(i32::max((x - from.0).abs(), (y - from.1).abs()), coast + 1);
+                        if length > MIN_RAIL_LENGTH {
+                            candidates.push(((x - dir.x * length, y - dir.y * length), exit));
+                        }
+                    }
+                }
+            }
+        }
+
+        for _ in 0..MAX_SHORTCUT_TRIES {
+            if candidates.is_empty() {
+                break;
+            }
+            let (from, exit) =
+                candidates.remove((rng.roll_dice(1, candidates.len() as i32) - 1) as usize);
+
+            let mut attempt = build_data.map.clone();
+            if lay_run(&mut attempt, start_idx, from, (exit.x, exit.y))
+                && has_rail_shortcut(&mut attempt, &self.physics, start_idx)
+            {
+                build_data.map = attempt;
+                build_data.take_snapshot();
+                return;
+            }
+        }
+
+        // no room for a rail into the stairs, so move the stairs to the end
+        // of the ride that leaves us farthest from the start instead
+        self.move_stairs_to_rail(build_data);
+    }
+
+    fn move_stairs_to_rail(&mut self, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        let map = &mut build_data.map;
+        let rides = rail_rides(map, &self.physics, start_idx);
+
+        let map_starts = vec![start_idx];
+        let walk = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+        let farthest = rides
+            .iter()
+            .map(|ride| map.xy_flat(ride.exit.x, ride.exit.y))
+            .filter(|idx| map.tiles[*idx] == TileType::Floor && walk.map[*idx] != f32::MAX)
+            .max_by(|a, b| walk.map[*a].total_cmp(&walk.map[*b]).then(b.cmp(a)));
+
+        if let Some(exit_idx) = farthest {
+            for tile in map.tiles.iter_mut() {
+                if *tile == TileType::DownStairs {
+                    *tile = TileType::Floor;
+                }
+            }
+            map.tiles[exit_idx] = TileType::DownStairs;
+            build_data.take_snapshot();
+        }
+    }
+}
+
+// walk from (x, y) in direction (dx, dy) for as long as we stay on floor,
+// crossing any rails on the way
+fn extend_run(map: &Map, x: i32, y: i32, dx: i32, dy: i32) -> (i32, i32) {
+    let (mut x, mut y) = (x, y);
+    loop {
+        let (nx, ny) = (x + dx, y + dy);
+        if nx < 1 || nx > map.width - 2 || ny < 1 || ny > map.height - 2 {
+            return (x, y);
+        }
+        match map.tiles[map.xy_flat(nx, ny)] {
+            TileType::Floor | TileType::Rail { .. } => {}
+            _ => return (x, y),
+        }
+        x = nx;
+        y = ny;
+    }
+}
+
+// rails the run between `from` and `to`, leaving the tile at each end bare
+// so there is somewhere to get on and off. False, leaving the map alone, if
+// it's too short or can't be made rideable without cutting anything off
+fn lay_run(map: &mut Map, start_idx: usize, from: (i32, i32), to: (i32, i32)) -> bool {
+    let line = rltk::line2d_bresenham(Point::new(from.0, from.1), Point::new(to.0, to.1));
+    if (line.len() as i32) - 2 < MIN_RAIL_LENGTH {
+        return false;
+    }
+
+    let mut laid = map.clone();
+    if !grade_ramp(&mut laid, &line) {
+        return false;
+    }
+    if laid.heights != map.heights
+        && !reachable_tiles(&mut laid, start_idx).is_superset(&reachable_tiles(map, start_idx))
+    {
+        return false;
+    }
+
+    let (first, last) = (line[1], line[line.len() - 2]);
+    apply_rail(&mut laid, first.x, first.y, last.x, last.y);
+    *map = laid;
+    true
+}
+
+// leaves runs we can already ride alone, anything else is evened out into a
+// ramp between the heights at either end. Existing rails and the stairs keep
+// their height
+fn grade_ramp(map: &mut Map, line: &[Point]) -> bool {
+    if line
+        .windows(2)
+        .all(|step| map.is_safe_step(step[0], step[1]))
+    {
+        return true;
+    }
+
+    let steps = line.len() as i32 - 1;
+    let low = map.heights[map.xy_flat(line[0].x, line[0].y)];
+    let high = map.heights[map.xy_flat(line[steps as usize].x, line[steps as usize].y)];
+    if (high - low).abs() > steps * MAX_CLIMB {
+        return false;
+    }
+
+    for (i, point) in line.iter().enumerate() {
+        let idx = map.xy_flat(point.x, point.y);
+        let height = low + ((high - low) as f32 * i as f32 / steps as f32).round() as i32;
+        if map.heights[idx] != height && map.tiles[idx] != TileType::Floor {
+            return false;
+        }
+        map.heights[idx] = height;
+    }
+    true
+}
+
+fn reachable_tiles(map: &mut Map, start_idx: usize) -> HashSet<usize> {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let map_starts = vec![start_idx];
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+    dijkstra_map
+        .map
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| **d != f32::MAX)
+        .map(|(idx, _)| idx)
+        .collect()
+}
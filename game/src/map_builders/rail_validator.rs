@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use rltk::{Point, PointF};
+
+use crate::{
+    map::{Map, TileType},
+    movement_system::{MotionState, MovementSystem, RAIL_SPEED_DAMP},
+    physics_config::Physics,
+};
+
+// from this deep on every level needs a rail that drops us off by the stairs
+pub const SHORTCUT_DEPTH: i32 = 3;
+// rides shorter than this don't count as a shortcut
+pub const MIN_SHORTCUT_RIDE: usize = 6;
+// how far from the down stairs a ride may leave us and still count
+pub const SHORTCUT_RANGE: f32 = 3.0;
+
+// gives up on rides that somehow never end
+const MAX_RIDE_TURNS: i32 = 200;
+
+// where a ride went and the tile we got off at
+pub struct RailRide {
+    pub rails: Vec<Point>,
+    pub exit: Point,
+}
+
+// the speed a player settles at after pushing in one direction for a while
+pub fn walking_speed(physics: &Physics) -> f32 {
+    f32::min(physics.inst / (1.0 - physics.speed_damp), physics.max_speed)
+}
+
+// the speed we board a rail at from `entry`. Slow ground there holds us
+// back, slippery ground only helps if the whole run-up is covered, so we
+// count on whichever is slower
+pub fn boarding_speed(map: &Map, physics: &Physics, entry: Point) -> f32 {
+    let underfoot = map.surface_at(entry).apply(*physics);
+    f32::min(walking_speed(physics), walking_speed(&underfoot))
+}
+
+fn rail_dir(map: &Map, point: Point) -> Option<Point> {
+    match map.tiles[map.xy_flat(point.x, point.y)] {
+        TileType::Rail { xdir, ydir } => Some(Point::new(xdir, ydir)),
+        _ => None,
+    }
+}
+
+// steps onto the rail at `first` from `entry` at boarding speed and coasts
+// under the movement rules. None if the rail can't be boarded that way or
+// the ride ends with us on the ground
+pub fn ride_rail(map: &Map, physics: &Physics, entry: Point, first: Point) -> Option<RailRide> {
+    let dir = rail_dir(map, first)?;
+    let (dx, dy) = (first.x - entry.x, first.y - entry.y);
+    if !MovementSystem::is_along_rail(dx, dy, dir.x, dir.y) || !map.is_safe_step(entry, first) {
+        return None;
+    }
+
+    let mut state = MotionState {
+        pos: first,
+        vel: PointF::new(dx as f32, dy as f32).normalized() * boarding_speed(map, physics, entry),
+        bal: PointF::new(0.0, 0.0),
+        grind: Some(dir),
+    };
+    let mut rails = vec![first];
+
+    for _ in 0..MAX_RIDE_TURNS {
+        let next = MovementSystem::simulate_turn(map, physics, state, None)?;
+
+        // fast enough and we cross several rails a turn
+        if next.pos != state.pos {
+            rails.extend(
+                rltk::line2d_bresenham(state.pos, next.pos)
+                    .into_iter()
+                    .skip(1)
+                    .filter(|p| rail_dir(map, *p).is_some()),
+            );
+        }
+
+        if next.grind.is_none() && (next.pos == state.pos || rail_dir(map, next.pos).is_none()) {
+            // stopped, or carried off the end of the rail
+            return Some(RailRide {
+                rails,
+                exit: next.pos,
+            });
+        }
+        // otherwise we're crossing another rail and momentum carries us over
+        state = next;
+    }
+
+    Some(RailRide {
+        rails,
+        exit: state.pos,
+    })
+}
+
+// how many tiles a ride carries us from the moment we step onto a rail at
+// walking speed without pushing
+pub fn coasting_distance(physics: &Physics) -> i32 {
+    let mut speed = walking_speed(physics);
+    let mut distance = 0;
+    loop {
+        speed *= RAIL_SPEED_DAMP;
+        if speed <= physics.zero_speed {
+            return distance;
+        }
+        distance += speed.round() as i32;
+    }
+}
+
+// every ride of at least MIN_SHORTCUT_RIDE rails that can be boarded from
+// somewhere we can walk to from start_idx
+pub fn rail_rides(map: &mut Map, physics: &Physics, start_idx: usize) -> Vec<RailRide> {
+    map.blocked_tiles.clear();
+    map.populate_blocked();
+
+    let map_starts = vec![start_idx];
+    let walk = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+    let mut rides = Vec::new();
+
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        let dir = match tile {
+            TileType::Rail { xdir, ydir } => Point::new(*xdir, *ydir),
+            _ => continue,
+        };
+        let first = Point::new(idx as i32 % map.width, idx as i32 / map.width);
+
+        for entry in [first - dir, first + dir] {
+            if entry.x < 0 || entry.x > map.width - 1 || entry.y < 0 || entry.y > map.height - 1 {
+                continue;
+            }
+            let entry_idx = map.xy_flat(entry.x, entry.y);
+            if map.tiles[entry_idx] == TileType::Wall || walk.map[entry_idx] == f32::MAX {
+                continue;
+            }
+
+            if let Some(ride) = ride_rail(map, physics, entry, first) {
+                if ride.rails.len() >= MIN_SHORTCUT_RIDE {
+                    rides.push(ride);
+                }
+            }
+        }
+    }
+
+    rides
+}
+
+// true if some rail we can walk to from start_idx carries us to within
+// SHORTCUT_RANGE of the down stairs
+pub fn has_rail_shortcut(map: &mut Map, physics: &Physics, start_idx: usize) -> bool {
+    let stairs = match map.tiles.iter().position(|t| *t == TileType::DownStairs) {
+        Some(idx) => idx,
+        None => return false,
+    };
+
+    let mut near_stairs: HashMap<usize, bool> = HashMap::new();
+    for ride in rail_rides(map, physics, start_idx) {
+        let exit_idx = map.xy_flat(ride.exit.x, ride.exit.y);
+        let close = *near_stairs.entry(exit_idx).or_insert_with(|| {
+            let exit_starts = vec![exit_idx];
+            let from_exit = rltk::DijkstraMap::new(
+                map.width,
+                map.height,
+                &exit_starts,
+                &*map,
+                SHORTCUT_RANGE + 1.0,
+            );
+            from_exit.map[stairs] <= SHORTCUT_RANGE
+        });
+        if close {
+            return true;
+        }
+    }
+
+    false
+}
@@ -0,0 +1,61 @@
+use rltk::RandomNumberGenerator;
+
+use crate::map::TileType;
+
+use super::{common::generate_spawn_regions, BuilderMap, MetaMapBuilder};
+
+// populates every room but the one the player starts in
+#[derive(Default)]
+pub struct RoomSpawner {}
+
+impl MetaMapBuilder for RoomSpawner {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data
+            .rooms
+            .as_ref()
+            .expect("Unable to spawn in rooms without rooms");
+        let map = &build_data.map;
+
+        for room in rooms.iter().skip(1) {
+            let mut region = Vec::new();
+            for y in room.y1 + 1..room.y2 {
+                for x in room.x1 + 1..room.x2 {
+                    let idx = map.xy_flat(x, y);
+                    // rooms may have been partly walled off by later passes
+                    if map.tiles[idx] == TileType::Floor {
+                        region.push(idx);
+                    }
+                }
+            }
+            build_data.spawn_regions.push(region);
+        }
+    }
+}
+
+impl RoomSpawner {
+    pub fn new() -> RoomSpawner {
+        RoomSpawner {}
+    }
+}
+
+// populates noise partitioned areas of the reachable floor, for maps
+// without rooms
+#[derive(Default)]
+pub struct NoiseRegionSpawner {}
+
+impl MetaMapBuilder for NoiseRegionSpawner {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let distances = build_data
+            .distances
+            .as_ref()
+            .expect("Unable to find distances from the start, cull unreachable tiles first");
+        let regions = generate_spawn_regions(&build_data.map, rng, distances);
+        build_data.spawn_regions.extend(regions.into_values());
+    }
+}
+
+impl NoiseRegionSpawner {
+    pub fn new() -> NoiseRegionSpawner {
+        NoiseRegionSpawner {}
+    }
+}
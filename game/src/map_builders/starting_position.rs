@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::map::TileType;
+
+use super::{BuilderMap, MetaMapBuilder};
+
+// share of the floor the start has to be connected to
+const MIN_START_REGION: f32 = 0.5;
+
+// starts the player in the middle of the first room
+#[derive(Default)]
+pub struct RoomBasedStartingPosition {}
+
+impl MetaMapBuilder for RoomBasedStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data
+            .rooms
+            .as_ref()
+            .expect("Unable to use a room based start without rooms");
+        build_data.starting_position = Some(rooms[0].center());
+    }
+}
+
+impl RoomBasedStartingPosition {
+    pub fn new() -> RoomBasedStartingPosition {
+        RoomBasedStartingPosition {}
+    }
+}
+
+// starts the player on the floor tile closest to the middle of the map that
+// isn't stuck in a small pocket, falling back to the biggest pocket there is
+#[derive(Default)]
+pub struct CenterStartingPosition {}
+
+impl MetaMapBuilder for CenterStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        map.blocked_tiles.clear();
+        map.populate_blocked();
+
+        let center = Point::new(map.width / 2, map.height / 2);
+        let mut candidates = map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile == TileType::Floor)
+            .map(|(i, _)| {
+                let point = Point::new(i as i32 % map.width, i as i32 / map.width);
+                (
+                    i,
+                    rltk::DistanceAlg::PythagorasSquared.distance2d(center, point),
+                )
+            })
+            .collect::<Vec<(usize, f32)>>();
+        candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let floor_count = candidates.len();
+        let mut seen = HashSet::new();
+        let mut best: Option<(usize, usize)> = None;
+        for (idx, _) in candidates.iter() {
+            if seen.contains(idx) {
+                continue;
+            }
+
+            let dijkstra_map =
+                rltk::DijkstraMap::new(map.width, map.height, &[*idx], &*map, 1000.0);
+            let region = candidates
+                .iter()
+                .filter(|(i, _)| dijkstra_map.map[*i] != f32::MAX)
+                .map(|(i, _)| *i)
+                .collect::<Vec<usize>>();
+
+            if best.is_none_or(|(_, size)| region.len() > size) {
+                best = Some((*idx, region.len()));
+            }
+            if region.len() as f32 >= floor_count as f32 * MIN_START_REGION {
+                break;
+            }
+            seen.extend(region);
+        }
+
+        let (start_idx, _) = best.expect("Unable to start on a map without floor");
+        build_data.starting_position = Some(Point::new(
+            start_idx as i32 % map.width,
+            start_idx as i32 / map.width,
+        ));
+    }
+}
+
+impl CenterStartingPosition {
+    pub fn new() -> CenterStartingPosition {
+        CenterStartingPosition {}
+    }
+}
@@ -0,0 +1,91 @@
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::{
+    map::{Surface, TileType},
+    random_table::RandomTable,
+};
+
+use super::{BuilderMap, MetaMapBuilder};
+
+const MAX_PATCHES: i32 = 8;
+// patches keep this far from the start so we don't begin the level sliding
+const START_CLEARANCE: f32 = 3.0;
+
+// spills patches of ice, mud, oil, grating and carpet over the floor, with
+// more of the treacherous ones the deeper we go
+#[derive(Default)]
+pub struct SurfaceBuilder {}
+
+impl MetaMapBuilder for SurfaceBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let depth = build_data.map.depth;
+        let table = RandomTable::new()
+            .add("Mud", 3)
+            .add("Grating", 3)
+            .add("Carpet", 3)
+            .add("Ice", 1 + depth)
+            .add("Oil", depth - 1);
+
+        let width = build_data.map.width;
+        let start_idx = build_data.starting_idx() as i32;
+        let start = Point::new(start_idx % width, start_idx / width);
+        let floors = build_data
+            .map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(idx, tile)| {
+                let point = Point::new(*idx as i32 % width, *idx as i32 / width);
+                **tile == TileType::Floor
+                    && rltk::DistanceAlg::Pythagoras.distance2d(point, start) > START_CLEARANCE
+            })
+            .map(|(idx, _)| idx as i32)
+            .collect::<Vec<i32>>();
+        if floors.is_empty() {
+            return;
+        }
+
+        let n_patches = i32::min(2 + depth, MAX_PATCHES);
+        for _ in 0..n_patches {
+            let surface = match table.roll(rng).as_deref() {
+                Some("Mud") => Surface::Mud,
+                Some("Grating") => Surface::Grating,
+                Some("Carpet") => Surface::Carpet,
+                Some("Ice") => Surface::Ice,
+                Some("Oil") => Surface::Oil,
+                _ => continue,
+            };
+
+            // a short drunken walk from a random floor tile, covering the
+            // floor it stumbles over
+            let idx = floors[(rng.roll_dice(1, floors.len() as i32) - 1) as usize];
+            let mut point = Point::new(idx % width, idx / width);
+            for _ in 0..rng.range(10, 40) {
+                let map = &mut build_data.map;
+                let idx = map.xy_flat(point.x, point.y);
+                if map.tiles[idx] == TileType::Floor
+                    && rltk::DistanceAlg::Pythagoras.distance2d(point, start) > START_CLEARANCE
+                {
+                    map.surfaces[idx] = surface;
+                }
+
+                let next = match rng.roll_dice(1, 4) {
+                    1 => Point::new(point.x - 1, point.y),
+                    2 => Point::new(point.x + 1, point.y),
+                    3 => Point::new(point.x, point.y - 1),
+                    _ => Point::new(point.x, point.y + 1),
+                };
+                if next.x > 0 && next.x < map.width - 1 && next.y > 0 && next.y < map.height - 1 {
+                    point = next;
+                }
+            }
+            build_data.take_snapshot();
+        }
+    }
+}
+
+impl SurfaceBuilder {
+    pub fn new() -> SurfaceBuilder {
+        SurfaceBuilder {}
+    }
+}
@@ -0,0 +1,21 @@
+use rltk::RandomNumberGenerator;
+
+use super::{common::apply_terrain, BuilderMap, MetaMapBuilder};
+
+// raises rolling terrain and platforms without cutting off much of the level
+#[derive(Default)]
+pub struct TerrainBuilder {}
+
+impl MetaMapBuilder for TerrainBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start_idx = build_data.starting_idx();
+        apply_terrain(&mut build_data.map, rng, start_idx);
+        build_data.take_snapshot();
+    }
+}
+
+impl TerrainBuilder {
+    pub fn new() -> TerrainBuilder {
+        TerrainBuilder {}
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+
+use crate::map::{Map, TileType};
+
+// directions into `MapChunk::exits` and `MapChunk::compatible_with`
+pub const NORTH: usize = 0;
+pub const SOUTH: usize = 1;
+pub const WEST: usize = 2;
+pub const EAST: usize = 3;
+
+// a pattern along with which patterns may sit next to it on each side
+#[derive(Clone)]
+pub struct MapChunk {
+    pub pattern: Vec<TileType>,
+    pub exits: [Vec<bool>; 4],
+    pub has_exits: bool,
+    pub compatible_with: [Vec<usize>; 4],
+}
+
+pub fn opposite(direction: usize) -> usize {
+    match direction {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        WEST => EAST,
+        _ => WEST,
+    }
+}
+
+pub fn tile_idx_in_chunk(chunk_size: i32, x: i32, y: i32) -> usize {
+    ((y * chunk_size) + x) as usize
+}
+
+// cuts the sample into chunk_size squares. Rails and stairs are learned as
+// plain floor, later stages put them back
+pub fn build_patterns(
+    map: &Map,
+    chunk_size: i32,
+    include_flipping: bool,
+    dedupe: bool,
+) -> Vec<Vec<TileType>> {
+    let chunks_x = map.width / chunk_size;
+    let chunks_y = map.height / chunk_size;
+    let mut patterns = Vec::new();
+
+    let tile_at = |x: i32, y: i32| match map.tiles[map.xy_flat(x, y)] {
+        TileType::Wall => TileType::Wall,
+        _ => TileType::Floor,
+    };
+
+    for cy in 0..chunks_y {
+        for cx in 0..chunks_x {
+            let start_x = cx * chunk_size;
+            let start_y = cy * chunk_size;
+
+            let mut flips: Vec<(bool, bool)> = vec![(false, false)];
+            if include_flipping {
+                flips.extend([(true, false), (false, true), (true, true)]);
+            }
+
+            for (flip_x, flip_y) in flips {
+                let mut pattern = Vec::new();
+                for y in 0..chunk_size {
+                    for x in 0..chunk_size {
+                        let sx = if flip_x {
+                            start_x + chunk_size - 1 - x
+                        } else {
+                            start_x + x
+                        };
+                        let sy = if flip_y {
+                            start_y + chunk_size - 1 - y
+                        } else {
+                            start_y + y
+                        };
+                        pattern.push(tile_at(sx, sy));
+                    }
+                }
+                patterns.push(pattern);
+            }
+        }
+    }
+
+    if dedupe {
+        let mut seen = HashSet::new();
+        patterns.retain(|p| seen.insert(p.clone()));
+    }
+
+    patterns
+}
+
+// works out where each pattern can be entered from and which patterns line
+// up with it on every side
+pub fn patterns_to_constraints(patterns: Vec<Vec<TileType>>, chunk_size: i32) -> Vec<MapChunk> {
+    let mut constraints: Vec<MapChunk> = patterns
+        .into_iter()
+        .map(|pattern| {
+            let mut exits: [Vec<bool>; 4] = Default::default();
+            for i in 0..chunk_size {
+                let open = |x: i32, y: i32| {
+                    pattern[tile_idx_in_chunk(chunk_size, x, y)] == TileType::Floor
+                };
+                exits[NORTH].push(open(i, 0));
+                exits[SOUTH].push(open(i, chunk_size - 1));
+                exits[WEST].push(open(0, i));
+                exits[EAST].push(open(chunk_size - 1, i));
+            }
+            let has_exits = exits.iter().any(|side| side.iter().any(|e| *e));
+
+            MapChunk {
+                pattern,
+                exits,
+                has_exits,
+                compatible_with: Default::default(),
+            }
+        })
+        .collect();
+
+    let snapshot = constraints.clone();
+    for chunk in constraints.iter_mut() {
+        for (j, potential) in snapshot.iter().enumerate() {
+            if !chunk.has_exits {
+                // a solid chunk can sit next to anything
+                for direction in 0..4 {
+                    chunk.compatible_with[direction].push(j);
+                }
+                continue;
+            }
+
+            for direction in 0..4 {
+                let side = &chunk.exits[direction];
+                let other = &potential.exits[opposite(direction)];
+                let has_any = side.iter().any(|e| *e);
+
+                let fits = if has_any {
+                    side.iter().zip(other.iter()).any(|(a, b)| *a && *b)
+                } else {
+                    !other.iter().any(|e| *e)
+                };
+                if fits {
+                    chunk.compatible_with[direction].push(j);
+                }
+            }
+        }
+    }
+
+    constraints
+}
+
+pub fn render_pattern_to_map(
+    map: &mut Map,
+    chunk: &MapChunk,
+    chunk_size: i32,
+    start_x: i32,
+    start_y: i32,
+) {
+    for y in 0..chunk_size {
+        for x in 0..chunk_size {
+            let idx = map.xy_flat(start_x + x, start_y + y);
+            map.tiles[idx] = chunk.pattern[tile_idx_in_chunk(chunk_size, x, y)];
+        }
+    }
+}
@@ -0,0 +1,110 @@
+pub mod constraints;
+pub mod solver;
+
+use rltk::{Point, RandomNumberGenerator};
+
+use crate::{
+    map::{Map, TileType},
+    prefabs::RawPrefab,
+};
+
+use self::{
+    constraints::{build_patterns, patterns_to_constraints},
+    solver::Solver,
+};
+use super::{BuilderMap, MapBuilder};
+
+const CHUNK_SIZE: i32 = 8;
+const MAX_TRIES: i32 = 10;
+// anything with less floor than this is thrown away and collapsed again
+const MIN_FLOOR_PERCENT: f32 = 0.25;
+
+// where the patterns are learned from
+pub enum WaveformSample {
+    Builder(Box<dyn MapBuilder>),
+    Prefab(RawPrefab),
+}
+
+// learns chunk_size patterns from a sample map and collapses a new level
+// out of them, falling back to the sample if that keeps failing
+pub struct WaveformCollapseBuilder {
+    sample: WaveformSample,
+}
+
+impl MapBuilder for WaveformCollapseBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let depth = build_data.map.depth;
+        let sample = match &mut self.sample {
+            WaveformSample::Builder(builder) => {
+                let mut sample_data = BuilderMap::new(depth);
+                builder.build_map(rng, &mut sample_data);
+                build_data.history.extend(sample_data.history);
+                sample_data.map
+            }
+            WaveformSample::Prefab(prefab) => {
+                let mut map = Map::new(depth);
+                prefab.apply(&mut map, Point::new(0, 0), 0);
+                map
+            }
+        };
+        build_data.map = sample.clone();
+        build_data.take_snapshot();
+
+        let patterns = build_patterns(&sample, CHUNK_SIZE, true, true);
+        let constraints = patterns_to_constraints(patterns, CHUNK_SIZE);
+
+        for _ in 0..MAX_TRIES {
+            build_data.map = Map::new(depth);
+            let mut solver = Solver::new(constraints.clone(), CHUNK_SIZE, &build_data.map);
+            while !solver.iteration(&mut build_data.map, rng) {
+                build_data.take_snapshot();
+            }
+            build_data.take_snapshot();
+
+            let floor_count = build_data
+                .map
+                .tiles
+                .iter()
+                .filter(|t| **t == TileType::Floor)
+                .count();
+            if solver.possible
+                && floor_count as f32 >= build_data.map.tiles.len() as f32 * MIN_FLOOR_PERCENT
+            {
+                wall_in(&mut build_data.map);
+                build_data.take_snapshot();
+                return;
+            }
+        }
+
+        build_data.map = sample;
+        build_data.take_snapshot();
+    }
+}
+
+impl WaveformCollapseBuilder {
+    pub fn from_builder(builder: Box<dyn MapBuilder>) -> WaveformCollapseBuilder {
+        WaveformCollapseBuilder {
+            sample: WaveformSample::Builder(builder),
+        }
+    }
+
+    pub fn from_prefab(prefab: RawPrefab) -> WaveformCollapseBuilder {
+        WaveformCollapseBuilder {
+            sample: WaveformSample::Prefab(prefab),
+        }
+    }
+}
+
+// chunks at the edge can leave the border open
+fn wall_in(map: &mut Map) {
+    for x in 0..map.width {
+        let (top, bottom) = (map.xy_flat(x, 0), map.xy_flat(x, map.height - 1));
+        map.tiles[top] = TileType::Wall;
+        map.tiles[bottom] = TileType::Wall;
+    }
+    for y in 0..map.height {
+        let (left, right) = (map.xy_flat(0, y), map.xy_flat(map.width - 1, y));
+        map.tiles[left] = TileType::Wall;
+        map.tiles[right] = TileType::Wall;
+    }
+}
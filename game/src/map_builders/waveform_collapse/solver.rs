@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use rltk::RandomNumberGenerator;
+
+use crate::map::Map;
+
+use super::constraints::{render_pattern_to_map, MapChunk, EAST, NORTH, SOUTH, WEST};
+
+// collapses the map one chunk at a time, always next to chunks already
+// placed when it can
+pub struct Solver {
+    constraints: Vec<MapChunk>,
+    chunk_size: i32,
+    chunks: Vec<Option<usize>>,
+    chunks_x: i32,
+    chunks_y: i32,
+    remaining: Vec<usize>,
+    pub possible: bool,
+}
+
+impl Solver {
+    pub fn new(constraints: Vec<MapChunk>, chunk_size: i32, map: &Map) -> Solver {
+        let chunks_x = map.width / chunk_size;
+        let chunks_y = map.height / chunk_size;
+        let count = (chunks_x * chunks_y) as usize;
+
+        Solver {
+            constraints,
+            chunk_size,
+            chunks: vec![None; count],
+            chunks_x,
+            chunks_y,
+            remaining: (0..count).collect(),
+            possible: true,
+        }
+    }
+
+    fn chunk_idx(&self, x: i32, y: i32) -> usize {
+        ((y * self.chunks_x) + x) as usize
+    }
+
+    // the placed chunks around (x, y), with the side they are on as seen from them
+    fn placed_neighbors(&self, x: i32, y: i32) -> Vec<(usize, usize)> {
+        let mut neighbors = Vec::new();
+        for (dx, dy, from_side) in [(0, -1, SOUTH), (0, 1, NORTH), (-1, 0, EAST), (1, 0, WEST)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || nx >= self.chunks_x || ny < 0 || ny >= self.chunks_y {
+                continue;
+            }
+            if let Some(chunk) = self.chunks[self.chunk_idx(nx, ny)] {
+                neighbors.push((chunk, from_side));
+            }
+        }
+        neighbors
+    }
+
+    // places one chunk, true once there is nothing left to place or the
+    // constraints can't be met
+    pub fn iteration(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) -> bool {
+        if self.remaining.is_empty() || !self.possible {
+            return true;
+        }
+
+        let frontier = self
+            .remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, idx)| {
+                let (x, y) = (**idx as i32 % self.chunks_x, **idx as i32 / self.chunks_x);
+                !self.placed_neighbors(x, y).is_empty()
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+
+        let pick = if frontier.is_empty() {
+            rng.roll_dice(1, self.remaining.len() as i32) as usize - 1
+        } else {
+            frontier[rng.roll_dice(1, frontier.len() as i32) as usize - 1]
+        };
+        let idx = self.remaining.remove(pick);
+        let (x, y) = (idx as i32 % self.chunks_x, idx as i32 / self.chunks_x);
+
+        let mut options: Option<HashSet<usize>> = None;
+        for (neighbor, side) in self.placed_neighbors(x, y) {
+            let allowed = self.constraints[neighbor].compatible_with[side]
+                .iter()
+                .copied()
+                .collect::<HashSet<usize>>();
+            options = Some(match options {
+                None => allowed,
+                Some(current) => current.intersection(&allowed).copied().collect(),
+            });
+        }
+
+        let mut options = match options {
+            None => (0..self.constraints.len()).collect::<Vec<usize>>(),
+            Some(set) => set.into_iter().collect::<Vec<usize>>(),
+        };
+        if options.is_empty() {
+            self.possible = false;
+            return true;
+        }
+
+        // hash sets don't keep an order, sort so a seed always picks the same
+        options.sort_unstable();
+        let choice = options[rng.roll_dice(1, options.len() as i32) as usize - 1];
+        self.chunks[idx] = Some(choice);
+        render_pattern_to_map(
+            map,
+            &self.constraints[choice],
+            self.chunk_size,
+            x * self.chunk_size,
+            y * self.chunk_size,
+        );
+
+        self.remaining.is_empty()
+    }
+}
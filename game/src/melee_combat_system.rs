@@ -0,0 +1,117 @@
+use rltk::PointF;
+use specs::prelude::*;
+
+use crate::{
+    components::{CargoDamage, CombatStats, Name, SufferDamage, Velocity, WantsToMelee},
+    delivery_system::DeliverySystem,
+    gamelog::GameLog,
+};
+
+// each unit of speed adds this fraction of the attacker's power
+pub const RAM_FACTOR: f32 = 0.5;
+
+pub struct MeleeCombatSystem {}
+
+impl MeleeCombatSystem {
+    pub fn compute_damage(power: i32, defense: i32, speed: f32) -> i32 {
+        let power = (power as f32 * (1.0 + speed * RAM_FACTOR)).round() as i32;
+        i32::max(0, power - defense)
+    }
+}
+
+impl<'a> System<'a> for MeleeCombatSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, WantsToMelee>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, CargoDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut log,
+            mut wants_melee,
+            names,
+            combat_stats,
+            mut vels,
+            mut damages,
+            mut cargo_damages,
+        ) = data;
+
+        let mut attackers = Vec::new();
+        for (entity, wants, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join()
+        {
+            if stats.hp <= 0 {
+                continue;
+            }
+
+            let target_stats = combat_stats.get(wants.target);
+            let target_name = names.get(wants.target);
+            if let (Some(target_stats), Some(target_name)) = (target_stats, target_name) {
+                if target_stats.hp <= 0 {
+                    continue;
+                }
+
+                let speed = vels.get(entity).map_or(0.0, |v| v.vel.mag());
+                let damage =
+                    MeleeCombatSystem::compute_damage(stats.power, target_stats.defense, speed);
+
+                if damage == 0 {
+                    log.entries.push(format!(
+                        "{} is unable to hurt {}",
+                        name.name, target_name.name
+                    ));
+                } else {
+                    log.entries.push(format!(
+                        "{} rams into {} for {} hp",
+                        name.name, target_name.name, damage
+                    ));
+                    SufferDamage::new_damage(&mut damages, wants.target, damage);
+                }
+
+                // getting rammed knocks around whatever the target carries
+                CargoDamage::new_damage(
+                    &mut cargo_damages,
+                    wants.target,
+                    DeliverySystem::cargo_damage(speed),
+                );
+
+                attackers.push(entity);
+            }
+        }
+
+        // the impact stops the attacker dead
+        for entity in attackers.iter() {
+            if let Some(vel) = vels.get_mut(*entity) {
+                vel.vel = PointF::new(0.0, 0.0);
+            }
+        }
+
+        wants_melee.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standing_still_deals_power_less_defense() {
+        assert_eq!(MeleeCombatSystem::compute_damage(10, 3, 0.0), 7);
+    }
+
+    #[test]
+    fn speed_adds_to_the_blow() {
+        assert_eq!(MeleeCombatSystem::compute_damage(10, 3, 2.0), 17);
+    }
+
+    #[test]
+    fn damage_is_never_negative() {
+        assert_eq!(MeleeCombatSystem::compute_damage(2, 10, 1.0), 0);
+    }
+}
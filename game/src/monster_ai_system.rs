@@ -0,0 +1,83 @@
+use rltk::{Algorithm2D, Point, PointF};
+use specs::prelude::*;
+
+use crate::{
+    components::{Balance, InstVel, Monster, Name, Position, Velocity, Viewshed},
+    map::Map,
+    movement_system::MovementSystem,
+    physics_config::PhysicsConfig,
+};
+
+pub struct MonsterAI {}
+
+impl<'a> System<'a> for MonsterAI {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Point>,
+        Entities<'a>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Balance>,
+        WriteStorage<'a, InstVel>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, PhysicsConfig>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            player_pos,
+            entities,
+            viewsheds,
+            monsters,
+            positions,
+            vels,
+            balances,
+            mut inst_vels,
+            names,
+            config,
+        ) = data;
+
+        for (entity, viewshed, _monster, pos, vel, balance) in (
+            &entities, &viewsheds, &monsters, &positions, &vels, &balances,
+        )
+            .join()
+        {
+            if !viewshed.visible_tiles.contains(&*player_pos) {
+                continue;
+            }
+
+            let path = rltk::a_star_search(
+                map.xy_flat(pos.point.x, pos.point.y),
+                map.xy_flat(player_pos.x, player_pos.y),
+                &*map,
+            );
+
+            // no path, or already on the player's tile
+            if !path.success || path.steps.len() < 2 {
+                continue;
+            }
+
+            let physics = map
+                .surface_at(pos.point)
+                .apply(config.for_entity(names.get(entity)));
+            let next = map.index_to_point2d(path.steps[1]);
+            let inst_v = PointF::new((next.x - pos.point.x) as f32, (next.y - pos.point.y) as f32)
+                .normalized()
+                * physics.inst;
+
+            // don't turn so hard that we fall over, coast and slow down instead
+            let balance = balance.bal * physics.balance_damp;
+            let last_vel = vel.vel * physics.speed_damp;
+            if MovementSystem::compute_balance(balance, last_vel, inst_v, &physics).mag()
+                >= physics.fallover
+            {
+                continue;
+            }
+
+            InstVel::new_inst_vel(&mut inst_vels, entity, inst_v);
+        }
+    }
+}
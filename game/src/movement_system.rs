@@ -1,41 +1,64 @@
+use std::collections::HashMap;
+
 use rltk::{Point, PointF, RandomNumberGenerator};
 use specs::prelude::*;
 use util::vec_ops::{self};
 
 use crate::{
-    components::{Balance, CombatStats, InstVel, Position, Velocity, WantsToFallover},
-    map::Map,
+    collision_system::BODY_CHECK_SPEED,
+    components::{
+        Balance, CargoDamage, CombatStats, Grinding, InstVel, Monster, Name, Position,
+        SufferDamage, Velocity, WantsToCollide, WantsToFallover, WantsToMelee,
+    },
+    delivery_system::DeliverySystem,
+    gamelog::GameLog,
+    map::{Map, TileType, MAX_CLIMB},
+    physics_config::{Physics, PhysicsConfig},
 };
 
-pub const PLAYER_INST: f32 = 0.77;
-pub const MONSTER_INST: f32 = 0.66;
-
-pub const MAX_SPEED: f32 = 3.0;
-pub const SPEED_DAMP: f32 = 0.66;
-
-pub const ZERO_SPEED: f32 = 0.5;
+// the rest of the physics are tuned in raws/physics.json
 pub const ZERO_BALANCE: f32 = 0.25;
 
-pub const BALANCE_DAMP: f32 = 0.5;
-pub const LEAN_FACTOR: f32 = 0.66;
-pub const FALLOVER: f32 = 1.33;
+// speed is damped less while grinding, and movement across the rail is
+// damped more
+pub const RAIL_SPEED_DAMP: f32 = 0.9;
+pub const RAIL_ORTHO_DAMP: f32 = 0.25;
+
+// damage per level dropped beyond what we can safely step down
+pub const DROP_DAMAGE: i32 = 2;
 
 pub struct FalloverSystem {}
 
 impl<'a> System<'a> for FalloverSystem {
     type SystemData = (
+        Entities<'a>,
         WriteStorage<'a, WantsToFallover>,
         WriteStorage<'a, Velocity>,
         WriteStorage<'a, Balance>,
         WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, CargoDamage>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut fallovers, mut vels, mut balances, mut combat_stats) = data;
-
-        for (_fall, vel, balance, _stats) in
-            (&mut fallovers, &mut vels, &mut balances, &mut combat_stats).join()
+        let (entities, mut fallovers, mut vels, mut balances, mut combat_stats, mut cargo_damages) =
+            data;
+
+        for (entity, _fall, vel, balance, _stats) in (
+            &entities,
+            &mut fallovers,
+            &mut vels,
+            &mut balances,
+            &mut combat_stats,
+        )
+            .join()
         {
+            // whatever we carry hits the ground with us
+            CargoDamage::new_damage(
+                &mut cargo_damages,
+                entity,
+                DeliverySystem::cargo_damage(vel.vel.mag()),
+            );
+
             vel.vel = PointF::new(0.0, 0.0);
             balance.bal = PointF::new(0.0, 0.0);
             //stats.hp = std::cmp::max(0, stats.hp - 1);
@@ -54,15 +77,47 @@ impl<'a> System<'a> for VelocityBalanceSystem {
         WriteStorage<'a, InstVel>,
         WriteStorage<'a, Balance>,
         WriteStorage<'a, WantsToFallover>,
+        ReadStorage<'a, Grinding>,
+        ReadStorage<'a, Position>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, PhysicsConfig>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut vels, mut inst_vels, mut balances, mut fallovers) = data;
+        let (
+            entities,
+            mut vels,
+            mut inst_vels,
+            mut balances,
+            mut fallovers,
+            grinding,
+            positions,
+            map,
+            names,
+            config,
+        ) = data;
 
-        for (entity, velocity, balance) in (&entities, &mut vels, &mut balances).join() {
-            // apply dampening
-            velocity.vel *= SPEED_DAMP;
-            balance.bal *= BALANCE_DAMP;
+        for (entity, velocity, balance, pos, name) in (
+            &entities,
+            &mut vels,
+            &mut balances,
+            positions.maybe(),
+            names.maybe(),
+        )
+            .join()
+        {
+            let grind = grinding.get(entity);
+            let physics = config.for_entity(name);
+            let physics = pos.map_or(physics, |p| map.surface_at(p.point).apply(physics));
+
+            // apply dampening, the surface underfoot decides how much
+            if grind.is_some() {
+                velocity.vel *= RAIL_SPEED_DAMP;
+            } else {
+                velocity.vel *= physics.speed_damp;
+            }
+            balance.bal *= physics.balance_damp;
 
             if let Some(inst_v) = inst_vels.get_mut(entity) {
                 let last_vel = velocity.vel;
@@ -80,22 +135,28 @@ impl<'a> System<'a> for VelocityBalanceSystem {
                 //console::log(format!("speed = ({}, {})", speed.speed.x, speed.speed.y));
 
                 // compute orthogonal movement's contribution to balance
-                balance.bal = MovementSystem::compute_balance(balance.bal, last_vel, instv_sum);
+                balance.bal =
+                    MovementSystem::compute_balance(balance.bal, last_vel, instv_sum, &physics);
+            }
+
+            // the rail carries the entity along its direction
+            if let Some(grind) = grind {
+                velocity.vel = MovementSystem::compute_rail_vel(velocity.vel, grind.dir);
             }
 
             let mag = velocity.vel.mag();
 
             // clamp to max_speed
-            if mag > MAX_SPEED {
-                velocity.vel *= MAX_SPEED / velocity.vel.mag();
+            if mag > physics.max_speed {
+                velocity.vel *= physics.max_speed / velocity.vel.mag();
 
             // zero speed below this threshold
-            } else if mag <= ZERO_SPEED {
+            } else if mag <= physics.zero_speed {
                 velocity.vel = PointF::new(0.0, 0.0);
             }
 
             // fallover when balance is too large
-            if balance.bal.mag() >= FALLOVER {
+            if balance.bal.mag() >= physics.fallover {
                 fallovers
                     .insert(entity, WantsToFallover {})
                     .expect("Unable to insert intent to fallover");
@@ -115,21 +176,253 @@ impl MovementSystem {
         vel + inst_vel
     }
 
-    pub fn compute_balance(balance: PointF, last_vel: PointF, inst_vel: PointF) -> PointF {
+    pub fn compute_balance(
+        balance: PointF,
+        last_vel: PointF,
+        inst_vel: PointF,
+        physics: &Physics,
+    ) -> PointF {
         let mut balance = balance;
         // entity leans in direction they were last moving and are not moving anymore
-        if last_vel.mag() > ZERO_SPEED && inst_vel.mag() > 0.01 {
+        if last_vel.mag() > physics.zero_speed && inst_vel.mag() > 0.01 {
             let direction_diff = last_vel - inst_vel;
             let units = vec_ops::discrete_jmp((direction_diff.x, direction_diff.y));
             let orthogonality = (2.0 * last_vel.mag() * inst_vel.mag() - last_vel.dot(inst_vel))
                 / (2.0 * last_vel.mag() * inst_vel.mag());
 
-            balance.x += units.1.signum() as f32 * orthogonality * LEAN_FACTOR;
-            balance.y += units.0.signum() as f32 * orthogonality * LEAN_FACTOR;
+            balance.x += units.1.signum() as f32 * orthogonality * physics.lean_factor;
+            balance.y += units.0.signum() as f32 * orthogonality * physics.lean_factor;
         }
 
         balance
     }
+
+    pub fn compute_rail_vel(vel: PointF, rail_dir: Point) -> PointF {
+        let dir = PointF::new(rail_dir.x as f32, rail_dir.y as f32).normalized();
+        let along = dir * vel.dot(dir);
+        let ortho = vel - along;
+
+        along + ortho * RAIL_ORTHO_DAMP
+    }
+
+    // the player and monsters are hostile to each other
+    pub fn is_hostile(
+        entity: Entity,
+        target: Entity,
+        player: Entity,
+        monsters: &ReadStorage<Monster>,
+    ) -> bool {
+        (entity == player && monsters.contains(target))
+            || (target == player && monsters.contains(entity))
+    }
+
+    fn move_combatant(
+        combatants: &mut HashMap<Point, Entity>,
+        entity: Entity,
+        from: Point,
+        to: Point,
+    ) {
+        if combatants.get(&from) == Some(&entity) {
+            combatants.remove(&from);
+            combatants.insert(to, entity);
+        }
+    }
+
+    // true if a move of (dx, dy) runs parallel to a rail pointing in (xdir, ydir)
+    pub fn is_along_rail(dx: i32, dy: i32, xdir: i32, ydir: i32) -> bool {
+        (dx == xdir && dy == ydir) || (dx == -xdir && dy == -ydir)
+    }
+
+    // plays out one turn of VelocityBalanceSystem and MovementSystem for a
+    // lone entity with `physics` pushing with `inst_vel`, or not pushing at
+    // all. None if the turn ends with it falling over
+    pub fn simulate_turn(
+        map: &Map,
+        physics: &Physics,
+        state: MotionState,
+        inst_vel: Option<PointF>,
+    ) -> Option<MotionState> {
+        MovementSystem::play_turn(map, physics, state, inst_vel).ok()
+    }
+
+    // simulate_turn, saying where and why we went down if we did
+    pub fn play_turn(
+        map: &Map,
+        physics: &Physics,
+        state: MotionState,
+        inst_vel: Option<PointF>,
+    ) -> Result<MotionState, Fall> {
+        let MotionState {
+            mut pos,
+            mut vel,
+            mut bal,
+            mut grind,
+        } = state;
+
+        let physics = map.surface_at(pos).apply(*physics);
+        if grind.is_some() {
+            vel *= RAIL_SPEED_DAMP;
+        } else {
+            vel *= physics.speed_damp;
+        }
+        bal *= physics.balance_damp;
+
+        if let Some(inst_vel) = inst_vel {
+            let last_vel = vel;
+            vel = MovementSystem::compute_vel_cached_sum(vel, inst_vel);
+            bal = MovementSystem::compute_balance(bal, last_vel, inst_vel, &physics);
+        }
+
+        if let Some(dir) = grind {
+            vel = MovementSystem::compute_rail_vel(vel, dir);
+        }
+
+        let mag = vel.mag();
+        if mag > physics.max_speed {
+            vel *= physics.max_speed / mag;
+        } else if mag <= physics.zero_speed {
+            vel = PointF::new(0.0, 0.0);
+        }
+
+        if bal.mag() >= physics.fallover {
+            return Err(Fall::Balance(pos));
+        } else if bal.mag() <= ZERO_BALANCE {
+            bal = PointF::new(0.0, 0.0);
+        }
+
+        if let Some(dir) = grind {
+            let rail_dir = PointF::new(dir.x as f32, dir.y as f32).normalized();
+            if vel.dot(rail_dir).abs() <= physics.zero_speed {
+                if let Some(floor) = map.nearest_floor(pos) {
+                    pos = floor;
+                }
+                return Ok(MotionState {
+                    pos,
+                    vel,
+                    bal,
+                    grind: None,
+                });
+            }
+        }
+
+        let riding = grind.is_some();
+        for next in MovementSystem::move_path(pos, vel, grind) {
+            // off the map, into a wall or up or down a ledge too high all
+            // knock us over
+            if next.x < 0 || next.x > map.width - 1 || next.y < 0 || next.y > map.height - 1 {
+                return Err(Fall::Crash(pos));
+            }
+            if map.blocked_tiles.contains(&next) || !map.is_safe_step(pos, next) {
+                return Err(Fall::Crash(next));
+            }
+
+            let (dx, dy) = (next.x - pos.x, next.y - pos.y);
+            pos = next;
+            grind = match map.tiles[map.xy_flat(pos.x, pos.y)] {
+                TileType::Rail { xdir, ydir }
+                    if MovementSystem::is_along_rail(dx, dy, xdir, ydir) =>
+                {
+                    Some(Point::new(xdir, ydir))
+                }
+                _ => None,
+            };
+            if riding && grind.is_none() {
+                break;
+            }
+        }
+
+        Ok(MotionState {
+            pos,
+            vel,
+            bal,
+            grind,
+        })
+    }
+
+    // plays out up to `turns` turns pushing with the same `inst_vel` every
+    // turn, or never pushing. Stops early once we come to rest, or with how
+    // we went down if we do
+    pub fn project_trajectory(
+        map: &Map,
+        physics: &Physics,
+        state: MotionState,
+        inst_vel: Option<PointF>,
+        turns: i32,
+    ) -> (Vec<MotionState>, Option<Fall>) {
+        let mut states = Vec::new();
+        let mut state = state;
+        for _ in 0..turns {
+            match MovementSystem::play_turn(map, physics, state, inst_vel) {
+                Ok(next) => {
+                    let resting = next.pos == state.pos && next.vel.mag() == 0.0;
+                    states.push(next);
+                    if resting {
+                        break;
+                    }
+                    state = next;
+                }
+                Err(fall) => return (states, Some(fall)),
+            }
+        }
+        (states, None)
+    }
+
+    // the tiles a move at `vel` crosses this turn, up to round(|vel|) of them
+    // along a bresenham line, or straight down the rail while grinding
+    pub fn move_path(from: Point, vel: PointF, grind: Option<Point>) -> Vec<Point> {
+        match grind {
+            Some(dir) => {
+                let along = vel.dot(PointF::new(dir.x as f32, dir.y as f32).normalized());
+                let sign = along.signum() as i32;
+                (1..=along.abs().round() as i32)
+                    .map(|i| Point::new(from.x + dir.x * sign * i, from.y + dir.y * sign * i))
+                    .collect()
+            }
+            None => {
+                let to = Point::new(
+                    (from.x as f32 + vel.x).round() as i32,
+                    (from.y as f32 + vel.y).round() as i32,
+                );
+                if to == from {
+                    return Vec::new();
+                }
+                rltk::line2d_bresenham(from, to)
+                    .into_iter()
+                    .skip(1)
+                    .collect()
+            }
+        }
+    }
+}
+
+// how and where a simulated turn ends with us on the ground
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fall {
+    // leaned too far over
+    Balance(Point),
+    // ran off the map, into something or over a ledge
+    Crash(Point),
+}
+
+// where an entity is and how it's moving, enough to play turns out without
+// touching the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionState {
+    pub pos: Point,
+    pub vel: PointF,
+    pub bal: PointF,
+    pub grind: Option<Point>,
+}
+
+impl MotionState {
+    pub fn at_rest(pos: Point) -> MotionState {
+        MotionState {
+            pos,
+            vel: PointF::new(0.0, 0.0),
+            bal: PointF::new(0.0, 0.0),
+            grind: None,
+        }
+    }
 }
 
 pub struct MovementSystem {}
@@ -144,6 +437,15 @@ impl<'a> System<'a> for MovementSystem {
         WriteStorage<'a, Position>,
         ReadStorage<'a, Velocity>,
         WriteStorage<'a, WantsToFallover>,
+        WriteStorage<'a, Grinding>,
+        WriteStorage<'a, SufferDamage>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, WantsToCollide>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, PhysicsConfig>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -156,51 +458,100 @@ impl<'a> System<'a> for MovementSystem {
             mut positions,
             vels,
             mut fallovers,
+            mut grinding,
+            mut damages,
+            mut log,
+            monsters,
+            combat_stats,
+            mut wants_melee,
+            mut wants_collide,
+            names,
+            config,
         ) = data;
 
+        // who can be attacked where, kept up to date as entities move
+        let mut combatants = HashMap::new();
+        for (entity, pos, _stats) in (&entities, &positions, &combat_stats).join() {
+            combatants.insert(pos.point, entity);
+        }
+
         let mut sort_by_vel = (&entities, &mut positions, &vels)
             .join()
             .collect::<Vec<_>>();
         sort_by_vel
             .sort_by(|l, r| (l.2.vel.mag().round() as i32).cmp(&(r.2.vel.mag().round() as i32)));
         for (entity, pos, vel) in sort_by_vel.iter_mut().rev() {
-            let x = (pos.point.x as f32 + vel.vel.x)
-                .clamp(pos.point.x as f32 - 1.0, pos.point.x as f32 + 1.0)
-                .round() as i32;
-            let y = (pos.point.y as f32 + vel.vel.y)
-                .clamp(pos.point.y as f32 - 1.0, pos.point.y as f32 + 1.0)
-                .round() as i32;
-
-            // nothing to update
-            if x == pos.point.x && y == pos.point.y {
-                continue;
+            let grind = grinding.get(*entity).map(|g| g.dir);
+            if let Some(dir) = grind {
+                let rail_dir = PointF::new(dir.x as f32, dir.y as f32).normalized();
+                let along = vel.vel.dot(rail_dir);
+
+                // out of speed: fall off the rail onto the nearest platform
+                if along.abs() <= config.for_entity(names.get(*entity)).zero_speed {
+                    grinding.remove(*entity);
+
+                    if let Some(floor) = map.nearest_floor(pos.point) {
+                        map.blocked_tiles.remove(&pos.point);
+                        MovementSystem::move_combatant(&mut combatants, *entity, pos.point, floor);
+                        pos.point = floor;
+
+                        if *entity == *player_entity {
+                            player_pos.x = floor.x;
+                            player_pos.y = floor.y;
+                        }
+
+                        map.blocked_tiles.insert(pos.point);
+                    }
+                    continue;
+                }
             }
 
-            let next = Point::new(x, y);
-            let mut blocked = false;
+            // cross every tile on the way, stopping at the first thing in it
+            for next in MovementSystem::move_path(pos.point, vel.vel, grind) {
+                let (x, y) = (next.x, next.y);
+
+                // running into a hostile entity attacks it instead of moving,
+                // unless we come in fast enough to body check it. Anything
+                // else that moves we collide with
+                if let Some(&target) = combatants.get(&next) {
+                    let closing = vels.get(target).map(|v| (vel.vel - v.vel).mag());
+                    if MovementSystem::is_hostile(*entity, target, *player_entity, &monsters)
+                        && closing.is_none_or(|speed| speed < BODY_CHECK_SPEED)
+                    {
+                        wants_melee
+                            .insert(*entity, WantsToMelee { target })
+                            .expect("Unable to insert intent to melee");
+                        break;
+                    }
+                    if closing.is_some() {
+                        wants_collide
+                            .insert(*entity, WantsToCollide { target })
+                            .expect("Unable to insert intent to collide");
+                        break;
+                    }
+                }
 
-            // check if we run run over the edge of the map
-            // insert a fallover intent and return
-            if x < 0 || x > map.width - 1 || y < 0 || y > map.height - 1 {
-                fallovers
-                    .insert(*entity, WantsToFallover {})
-                    .expect("Could not insert intent to fallover");
-                return;
-            // we encounter a blocked tile
-            } else if map.blocked_tiles.contains(&next) && next != pos.point {
-                blocked = true;
-            }
+                // running off the edge of the map, into something in the way
+                // or against a ledge too high to climb knocks us over
+                if x < 0
+                    || x > map.width - 1
+                    || y < 0
+                    || y > map.height - 1
+                    || map.blocked_tiles.contains(&next)
+                    || map.height_diff(pos.point, next) > MAX_CLIMB
+                {
+                    fallovers
+                        .insert(*entity, WantsToFallover {})
+                        .expect("Could not insert intent to fallover");
+                    break;
+                }
 
-            // fallover if we are off the map or still blocked
-            if x < 0 || x > map.width - 1 || y < 0 || y > map.height - 1 || blocked {
-                fallovers
-                    .insert(*entity, WantsToFallover {})
-                    .expect("Could not insert intent to fallover");
-            }
-            // update position
-            else {
                 //console::log(format!("next = ({}, {})", next.x, next.y));
+                let (dx, dy) = (x - pos.point.x, y - pos.point.y);
+                let drop = -map.height_diff(pos.point, next);
+
                 map.blocked_tiles.remove(&pos.point);
+                MovementSystem::move_combatant(&mut combatants, *entity, pos.point, next);
                 pos.point = next;
 
                 if *entity == *player_entity {
@@ -209,7 +560,82 @@ impl<'a> System<'a> for MovementSystem {
                 }
 
                 map.blocked_tiles.insert(pos.point);
+
+                // dropping off a ledge knocks us over and hurts
+                if drop > MAX_CLIMB {
+                    fallovers
+                        .insert(*entity, WantsToFallover {})
+                        .expect("Could not insert intent to fallover");
+                    SufferDamage::new_damage(
+                        &mut damages,
+                        *entity,
+                        (drop - MAX_CLIMB) * DROP_DAMAGE,
+                    );
+                    grinding.remove(*entity);
+
+                    if *entity == *player_entity {
+                        log.entries
+                            .push(format!("You drop {} levels and land hard", drop));
+                    }
+                    break;
+                }
+
+                // moving onto a rail in its direction locks us into grinding it,
+                // moving anywhere else takes us off the rail. Coming off the
+                // rail we were riding ends the move
+                let idx = map.xy_flat(x, y);
+                match map.tiles[idx] {
+                    TileType::Rail { xdir, ydir }
+                        if MovementSystem::is_along_rail(dx, dy, xdir, ydir) =>
+                    {
+                        grinding
+                            .insert(
+                                *entity,
+                                Grinding {
+                                    dir: Point::new(xdir, ydir),
+                                },
+                            )
+                            .expect("Unable to insert grinding");
+                    }
+                    _ => {
+                        grinding.remove(*entity);
+                        if grind.is_some() {
+                            break;
+                        }
+                    }
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_path_steps_over_every_tile_we_cross() {
+        let path = MovementSystem::move_path(Point::new(5, 5), PointF::new(2.2, 0.0), None);
+        assert_eq!(path, vec![Point::new(6, 5), Point::new(7, 5)]);
+    }
+
+    #[test]
+    fn move_path_is_empty_when_too_slow_to_leave_the_tile() {
+        let path = MovementSystem::move_path(Point::new(5, 5), PointF::new(0.3, -0.2), None);
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn move_path_follows_the_rail_when_grinding() {
+        // only the part of the velocity along the rail counts
+        let dir = Some(Point::new(1, 1));
+        let path = MovementSystem::move_path(Point::new(5, 5), PointF::new(2.0, 2.0), dir);
+        assert_eq!(
+            path,
+            vec![Point::new(6, 6), Point::new(7, 7), Point::new(8, 8)]
+        );
+
+        let path = MovementSystem::move_path(Point::new(5, 5), PointF::new(-1.0, -0.5), dir);
+        assert_eq!(path, vec![Point::new(4, 4)]);
+    }
+}
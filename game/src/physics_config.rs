@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{components::Name, gamelog::GameLog, raws::RawMaster, replay::Replay};
+
+pub const PHYSICS_ENV: &str = "DD_PHYSICS";
+
+// built in like the other raws, DD_PHYSICS points at a file to use instead
+// which can be tuned and reloaded while playing
+const PHYSICS_JSON: &str = include_str!("../raws/physics.json");
+
+// the player goes by this name in the entity overrides
+pub const PLAYER_NAME: &str = "Player";
+
+// how something speeds up, slows down and keeps its balance
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    // velocity added by a single push
+    pub inst: f32,
+    pub max_speed: f32,
+    // fraction of velocity kept from one turn to the next
+    pub speed_damp: f32,
+    // slower than this and we stop
+    pub zero_speed: f32,
+    // fraction of lean kept from one turn to the next
+    pub balance_damp: f32,
+    // how far turning tips us over
+    pub lean_factor: f32,
+    // leaning this far knocks us over
+    pub fallover: f32,
+}
+
+// whatever an entity type does differently from the defaults
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct PhysicsOverride {
+    pub inst: Option<f32>,
+    pub max_speed: Option<f32>,
+    pub speed_damp: Option<f32>,
+    pub zero_speed: Option<f32>,
+    pub balance_damp: Option<f32>,
+    pub lean_factor: Option<f32>,
+    pub fallover: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PhysicsConfig {
+    pub defaults: Physics,
+    // by name, the player is PLAYER_NAME and monsters go by their raws name
+    #[serde(default)]
+    pub entities: BTreeMap<String, PhysicsOverride>,
+}
+
+impl Physics {
+    fn with(&self, o: &PhysicsOverride) -> Physics {
+        Physics {
+            inst: o.inst.unwrap_or(self.inst),
+            max_speed: o.max_speed.unwrap_or(self.max_speed),
+            speed_damp: o.speed_damp.unwrap_or(self.speed_damp),
+            zero_speed: o.zero_speed.unwrap_or(self.zero_speed),
+            balance_damp: o.balance_damp.unwrap_or(self.balance_damp),
+            lean_factor: o.lean_factor.unwrap_or(self.lean_factor),
+            fallover: o.fallover.unwrap_or(self.fallover),
+        }
+    }
+
+    fn validate(&self, name: &str, errors: &mut Vec<String>) {
+        for (field, value) in [
+            ("inst", self.inst),
+            ("max_speed", self.max_speed),
+            ("zero_speed", self.zero_speed),
+            ("fallover", self.fallover),
+        ] {
+            if value <= 0.0 {
+                errors.push(format!("{}: {} must be above 0", name, field));
+            }
+        }
+        for (field, value) in [
+            ("speed_damp", self.speed_damp),
+            ("balance_damp", self.balance_damp),
+        ] {
+            if !(0.0..1.0).contains(&value) {
+                errors.push(format!(
+                    "{}: {} must be at least 0 and below 1",
+                    name, field
+                ));
+            }
+        }
+        if self.lean_factor < 0.0 {
+            errors.push(format!("{}: lean_factor can't be negative", name));
+        }
+        if self.zero_speed >= self.max_speed {
+            errors.push(format!("{}: zero_speed must be below max_speed", name));
+        }
+    }
+}
+
+impl PhysicsConfig {
+    // reads the physics and refuses to start the game if they don't make sense
+    pub fn load(raws: &RawMaster) -> PhysicsConfig {
+        PhysicsConfig::read(raws).unwrap_or_else(|errors| panic!("{}", errors))
+    }
+
+    fn read(raws: &RawMaster) -> Result<PhysicsConfig, String> {
+        let data = match env::var(PHYSICS_ENV) {
+            Ok(path) => fs::read_to_string(&path)
+                .map_err(|e| format!("Unable to read physics from {}: {}", path, e))?,
+            Err(_) => PHYSICS_JSON.to_string(),
+        };
+
+        let mut physics: PhysicsConfig =
+            serde_json::from_str(&data).map_err(|e| format!("Unable to parse physics: {}", e))?;
+
+        physics
+            .take_movement(raws)
+            .and_then(|_| physics.validate(raws))
+            .map_err(|errors| format!("Invalid physics:\n{}", errors.join("\n")))?;
+
+        Ok(physics)
+    }
+
+    // swaps in the physics as they are on disk now, keeping the old ones if
+    // the new ones don't load
+    pub fn reload(ecs: &mut World) {
+        let physics = PhysicsConfig::read(&ecs.fetch::<RawMaster>());
+        let entry = match physics {
+            Ok(physics) => {
+                PhysicsConfig::apply(ecs, physics);
+                "Physics reloaded".to_string()
+            }
+            Err(e) => e,
+        };
+        ecs.fetch_mut::<GameLog>().entries.push(entry);
+    }
+
+    // puts new physics in play, noting the change in the replay so it can be
+    // played back the same way
+    pub fn apply(ecs: &mut World, physics: PhysicsConfig) {
+        Replay::record_physics(ecs, &physics);
+        ecs.insert(physics);
+    }
+
+    // how hard a monster pushes can come from its raws, but only from one
+    // place
+    fn take_movement(&mut self, raws: &RawMaster) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for monster in raws.monsters.iter() {
+            if let Some(movement) = &monster.movement {
+                let o = self.entities.entry(monster.name.clone()).or_default();
+                if o.inst.is_some() {
+                    errors.push(format!(
+                        "{}: inst is set in both the raws and the physics",
+                        monster.name
+                    ));
+                }
+                o.inst = Some(movement.inst);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn validate(&self, raws: &RawMaster) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        self.defaults.validate("defaults", &mut errors);
+
+        for name in self.entities.keys() {
+            if name != PLAYER_NAME && raws.monster(name).is_none() {
+                errors.push(format!("{}: not the player or a monster", name));
+            }
+            self.for_name(name).validate(name, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn for_name(&self, name: &str) -> Physics {
+        match self.entities.get(name) {
+            Some(o) => self.defaults.with(o),
+            None => self.defaults,
+        }
+    }
+
+    pub fn for_entity(&self, name: Option<&Name>) -> Physics {
+        name.map_or(self.defaults, |n| self.for_name(&n.name))
+    }
+
+    pub fn player(&self) -> Physics {
+        self.for_name(PLAYER_NAME)
+    }
+}
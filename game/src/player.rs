@@ -1,18 +1,24 @@
 use rltk::{Point, PointF, Rltk, VirtualKeyCode};
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::Component;
 
-use crate::components::{InstVel, Item, Viewshed, WantsToPickUpItem};
+use crate::components::{
+    InBackpack, InstVel, Item, Name, Package, Viewshed, WantsToDropItem, WantsToPickUpItem,
+    WantsToUseItem,
+};
 use crate::gamelog::GameLog;
+use crate::gui::MainMenuSelection;
 use crate::map::{Map, TileType};
-use crate::movement_system::PLAYER_INST;
+use crate::physics_config::PhysicsConfig;
+use crate::replay::Replay;
 use crate::state::RunState;
 
 use super::components::Position;
 use super::state::State;
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Player {}
 
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
@@ -22,9 +28,10 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
 
     let player_entity = ecs.fetch_mut::<Entity>();
     let mut inst_vels = ecs.write_storage::<InstVel>();
+    let inst = ecs.fetch::<PhysicsConfig>().player().inst;
     let mut vel = PointF::new(delta_x as f32, delta_y as f32).normalized();
-    vel.x *= PLAYER_INST;
-    vel.y *= PLAYER_INST;
+    vel.x *= inst;
+    vel.y *= inst;
 
     InstVel::new_inst_vel(&mut inst_vels, *player_entity, vel);
 }
@@ -65,45 +72,97 @@ fn get_item(ecs: &mut World) {
     }
 }
 
+// everything the player can do that uses up a turn, in a form that can be
+// scripted or replayed
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PlayerAction {
+    Move { dx: i32, dy: i32 },
+    Wait,
+    PickUp,
+    UseItem { slot: usize, target: Option<Point> },
+    DropItem { slot: usize },
+    NextLevel,
+}
+
+// the player's items in the order the inventory menu lists them
+pub fn backpack_items(ecs: &World) -> Vec<Entity> {
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let names = ecs.read_storage::<Name>();
+
+    (&entities, &backpack, &names)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+        .map(|item| item.0)
+        .collect()
+}
+
+pub fn backpack_slot(ecs: &World, item: Entity) -> Option<usize> {
+    backpack_items(ecs).iter().position(|e| *e == item)
+}
+
+pub fn perform_action(ecs: &mut World, action: PlayerAction) -> RunState {
+    match action {
+        PlayerAction::Move { dx, dy } => {
+            try_move_player(dx, dy, ecs);
+        }
+        PlayerAction::Wait => {}
+        PlayerAction::PickUp => {
+            get_item(ecs);
+        }
+        PlayerAction::UseItem { slot, target } => {
+            let item = match backpack_items(ecs).get(slot) {
+                Some(item) => *item,
+                None => return RunState::AwaitingInput,
+            };
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToUseItem>()
+                .insert(player_entity, WantsToUseItem { item, target })
+                .expect("Unable to insert intent to use item");
+        }
+        PlayerAction::DropItem { slot } => {
+            let item = match backpack_items(ecs).get(slot) {
+                Some(item) => *item,
+                None => return RunState::AwaitingInput,
+            };
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToDropItem>()
+                .insert(player_entity, WantsToDropItem { item })
+                .expect("Unable to insert intent to drop item");
+        }
+        PlayerAction::NextLevel => {
+            if try_next_level(ecs) {
+                Replay::record(ecs, action);
+                return RunState::NextLevel;
+            }
+        }
+    }
+
+    Replay::record(ecs, action);
+    RunState::PlayerTurn
+}
+
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
+    let action = match ctx.key {
         None => {
             return RunState::AwaitingInput;
         }
         Some(key) => match key {
-            VirtualKeyCode::A | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs);
-            }
-            VirtualKeyCode::D | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs);
-            }
-            VirtualKeyCode::W | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs);
-            }
-            VirtualKeyCode::S | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs);
-            }
-            VirtualKeyCode::Q | VirtualKeyCode::Y => {
-                try_move_player(-1, -1, &mut gs.ecs);
-            }
-            VirtualKeyCode::E | VirtualKeyCode::U => {
-                try_move_player(1, -1, &mut gs.ecs);
-            }
-            VirtualKeyCode::Z | VirtualKeyCode::B => {
-                try_move_player(-1, 1, &mut gs.ecs);
-            }
-            VirtualKeyCode::C | VirtualKeyCode::N => {
-                try_move_player(1, 1, &mut gs.ecs);
-            }
-            VirtualKeyCode::Period | VirtualKeyCode::Key1 => {
-                return RunState::PlayerTurn;
-            }
+            VirtualKeyCode::A | VirtualKeyCode::H => PlayerAction::Move { dx: -1, dy: 0 },
+            VirtualKeyCode::D | VirtualKeyCode::L => PlayerAction::Move { dx: 1, dy: 0 },
+            VirtualKeyCode::W | VirtualKeyCode::K => PlayerAction::Move { dx: 0, dy: -1 },
+            VirtualKeyCode::S | VirtualKeyCode::J => PlayerAction::Move { dx: 0, dy: 1 },
+            VirtualKeyCode::Q | VirtualKeyCode::Y => PlayerAction::Move { dx: -1, dy: -1 },
+            VirtualKeyCode::E | VirtualKeyCode::U => PlayerAction::Move { dx: 1, dy: -1 },
+            VirtualKeyCode::Z | VirtualKeyCode::B => PlayerAction::Move { dx: -1, dy: 1 },
+            VirtualKeyCode::C | VirtualKeyCode::N => PlayerAction::Move { dx: 1, dy: 1 },
+            VirtualKeyCode::Period | VirtualKeyCode::Key1 => PlayerAction::Wait,
+            VirtualKeyCode::G => PlayerAction::PickUp,
+            VirtualKeyCode::Key0 => PlayerAction::NextLevel,
             VirtualKeyCode::Semicolon => {
                 return RunState::Looking;
             }
-            VirtualKeyCode::G => {
-                get_item(&mut gs.ecs);
-            }
             VirtualKeyCode::I => {
                 return RunState::ShowInventory;
             }
@@ -113,18 +172,33 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             VirtualKeyCode::Escape => {
                 return RunState::ShowHelpMenu { shown: false };
             }
-            VirtualKeyCode::Key0 => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
-                }
+            VirtualKeyCode::T => {
+                gs.trajectory_preview = gs.trajectory_preview.next();
+                gs.map_drawn = false;
+                gs.ecs.fetch_mut::<GameLog>().entries.push(format!(
+                    "Trajectory preview: {}",
+                    gs.trajectory_preview.name()
+                ));
+                return RunState::AwaitingInput;
+            }
+            // debug: pick up changes to raws/physics.json
+            VirtualKeyCode::F5 => {
+                PhysicsConfig::reload(&mut gs.ecs);
+                return RunState::AwaitingInput;
+            }
+            VirtualKeyCode::M => {
+                gs.redraw_menu = true;
+                return RunState::MainMenu {
+                    menu_selection: MainMenuSelection::SaveAndQuit,
+                };
             }
             _ => {
                 return RunState::AwaitingInput;
             }
         },
-    }
+    };
 
-    RunState::PlayerTurn
+    perform_action(&mut gs.ecs, action)
 }
 
 pub fn try_next_level(ecs: &mut World) -> bool {
@@ -132,6 +206,13 @@ pub fn try_next_level(ecs: &mut World) -> bool {
     let map = ecs.fetch::<Map>();
     let player_idx = map.xy_flat(player_pos.x, player_pos.y);
     if map.tiles[player_idx] == TileType::DownStairs {
+        // the stairs stay locked until the job on this level is done
+        if ecs.read_storage::<Package>().join().next().is_some() {
+            let mut log = ecs.fetch_mut::<GameLog>();
+            log.entries
+                .push("The stairs are locked until you deliver your package".to_string());
+            return false;
+        }
         return true;
     } else {
         let mut log = ecs.fetch_mut::<GameLog>();
@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+
+use rltk::Point;
+use serde::Deserialize;
+
+use crate::{
+    map::{Map, TileType, MAPHEIGHT, MAPWIDTH, MAX_HEIGHT},
+    map_builders::rail_validator::{has_rail_shortcut, SHORTCUT_DEPTH},
+    physics_config::Physics,
+    random_table::RandomTable,
+    raws::{RawMaster, RawSpawn},
+};
+
+pub const PREFABS_ENV: &str = "DD_PREFABS";
+
+// built in like the spawn raws, DD_PREFABS points at a file to use instead
+const PREFABS_JSON: &str = include_str!("../raws/prefabs.json");
+
+// a space in any layer leaves whatever is underneath alone
+pub const TRANSPARENT: char = ' ';
+
+// what a character in a prefab layer turns into
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawLegendEntry {
+    pub tile: Option<TileType>,
+    pub height: Option<i32>,
+    pub spawn: Option<String>,
+    #[serde(default)]
+    pub start: bool,
+}
+
+// a hand-drawn section of map, either a vault stamped into a generated
+// level or, with `level` set, a whole level. Layers are applied in order
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawPrefab {
+    pub name: String,
+    #[serde(default)]
+    pub level: bool,
+    pub spawn: RawSpawn,
+    pub layers: Vec<Vec<String>>,
+    pub legend: HashMap<char, RawLegendEntry>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct PrefabMaster {
+    pub prefabs: Vec<RawPrefab>,
+}
+
+impl RawPrefab {
+    pub fn width(&self) -> i32 {
+        self.layers[0][0].chars().count() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.layers[0].len() as i32
+    }
+
+    // every non-transparent character with its offset into the prefab
+    pub fn cells(&self) -> Vec<(Point, &RawLegendEntry)> {
+        let mut cells = Vec::new();
+        for layer in self.layers.iter() {
+            for (y, row) in layer.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    if let Some(entry) = self.legend.get(&c) {
+                        cells.push((Point::new(x as i32, y as i32), entry));
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    // stamps the prefab with its top left corner at `origin`. Heights are
+    // relative to `base`, tiles without one are flattened to it. Returns the
+    // spawns it asks for as (map index, name)
+    pub fn apply(
+        &self,
+        map: &mut Map,
+        origin: Point,
+        base: i32,
+    ) -> (Vec<(usize, String)>, Option<Point>) {
+        let mut spawns = Vec::new();
+        let mut start = None;
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let idx = map.xy_flat(origin.x + x, origin.y + y);
+                map.heights[idx] = base;
+            }
+        }
+
+        for (offset, entry) in self.cells() {
+            let point = origin + offset;
+            let idx = map.xy_flat(point.x, point.y);
+            if let Some(tile) = entry.tile {
+                map.tiles[idx] = tile;
+            }
+            if let Some(height) = entry.height {
+                map.heights[idx] = i32::min(base + height, MAX_HEIGHT);
+            }
+            if let Some(name) = &entry.spawn {
+                spawns.push((idx, name.clone()));
+            }
+            if entry.start {
+                start = Some(point);
+            }
+        }
+
+        (spawns, start)
+    }
+
+    fn validate(&self, raws: &RawMaster, physics: &Physics, errors: &mut Vec<String>) {
+        let name = &self.name;
+        self.spawn.validate(name, errors);
+
+        if self.layers.is_empty() || self.layers[0].is_empty() {
+            errors.push(format!("{}: needs at least one layer", name));
+            return;
+        }
+
+        let (width, height) = (self.width(), self.height());
+        for layer in self.layers.iter() {
+            if layer.len() as i32 != height
+                || layer.iter().any(|row| row.chars().count() as i32 != width)
+            {
+                errors.push(format!(
+                    "{}: every layer must be {}x{}",
+                    name, width, height
+                ));
+            }
+            for c in layer.iter().flat_map(|row| row.chars()) {
+                if c != TRANSPARENT && !self.legend.contains_key(&c) {
+                    errors.push(format!("{}: '{}' is not in the legend", name, c));
+                }
+            }
+        }
+
+        for (c, entry) in self.legend.iter() {
+            if *c == TRANSPARENT {
+                errors.push(format!(
+                    "{}: ' ' is transparent, it can't be in the legend",
+                    name
+                ));
+            }
+            if entry.height.is_some_and(|h| !(0..=MAX_HEIGHT).contains(&h)) {
+                errors.push(format!(
+                    "{}: '{}' height must be 0 to {}",
+                    name, c, MAX_HEIGHT
+                ));
+            }
+            if let Some(spawn) = &entry.spawn {
+                if raws.monster(spawn).is_none() && raws.item(spawn).is_none() {
+                    errors.push(format!("{}: '{}' spawns unknown {}", name, c, spawn));
+                }
+            }
+        }
+
+        let cells = self.cells();
+        let starts = cells.iter().filter(|(_, e)| e.start).count();
+        let has_stairs = cells
+            .iter()
+            .any(|(_, e)| e.tile == Some(TileType::DownStairs));
+
+        if self.level {
+            if width != MAPWIDTH as i32 || height != MAPHEIGHT as i32 {
+                errors.push(format!(
+                    "{}: levels must be {}x{}",
+                    name, MAPWIDTH, MAPHEIGHT
+                ));
+                return;
+            }
+            if starts != 1 || !has_stairs {
+                errors.push(format!("{}: levels need one start and down stairs", name));
+                return;
+            }
+            if !self.stairs_reachable() {
+                errors.push(format!("{}: the down stairs can't be reached", name));
+            } else if self.spawn.max_depth.is_none_or(|d| d >= SHORTCUT_DEPTH)
+                && !self.has_rail_shortcut(physics)
+            {
+                errors.push(format!(
+                    "{}: levels from depth {} on need a rail to the down stairs",
+                    name, SHORTCUT_DEPTH
+                ));
+            }
+        } else if starts > 0 || has_stairs {
+            errors.push(format!("{}: vaults can't hold the start or stairs", name));
+        }
+    }
+
+    // builds the level on an empty map, returning it with the start
+    fn level_map(&self) -> (Map, usize) {
+        let mut map = Map::new(1);
+        let (_, start) = self.apply(&mut map, Point::new(0, 0), 0);
+        let start = start.unwrap();
+        let start_idx = map.xy_flat(start.x, start.y);
+        (map, start_idx)
+    }
+
+    fn stairs_reachable(&self) -> bool {
+        let (mut map, start_idx) = self.level_map();
+
+        map.populate_blocked();
+        let map_starts = vec![start_idx];
+        let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &map, 1000.0);
+
+        map.tiles
+            .iter()
+            .enumerate()
+            .any(|(i, t)| *t == TileType::DownStairs && dijkstra_map.map[i] != f32::MAX)
+    }
+
+    fn has_rail_shortcut(&self, physics: &Physics) -> bool {
+        let (mut map, start_idx) = self.level_map();
+        has_rail_shortcut(&mut map, physics, start_idx)
+    }
+}
+
+impl PrefabMaster {
+    // reads the prefabs and refuses to start if they don't fit the map,
+    // spawn things the raws don't define or can't be ridden through with the
+    // player's physics
+    pub fn load(raws: &RawMaster, physics: &Physics) -> PrefabMaster {
+        let data = match env::var(PREFABS_ENV) {
+            Ok(path) => fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Unable to read prefabs from {}: {}", path, e)),
+            Err(_) => PREFABS_JSON.to_string(),
+        };
+
+        let prefabs: PrefabMaster = serde_json::from_str(&data)
+            .unwrap_or_else(|e| panic!("Unable to parse prefabs: {}", e));
+
+        if let Err(errors) = prefabs.validate(raws, physics) {
+            panic!("Invalid prefabs:\n{}", errors.join("\n"));
+        }
+
+        prefabs
+    }
+
+    pub fn validate(&self, raws: &RawMaster, physics: &Physics) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut names = HashSet::new();
+
+        for prefab in self.prefabs.iter() {
+            if !names.insert(prefab.name.clone()) {
+                errors.push(format!("{}: defined more than once", prefab.name));
+            }
+            prefab.validate(raws, physics, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn prefab(&self, name: &str) -> Option<&RawPrefab> {
+        self.prefabs.iter().find(|p| p.name == name)
+    }
+
+    pub fn vault_table(&self, depth: i32) -> RandomTable {
+        self.table(depth, false)
+    }
+
+    pub fn level_table(&self, depth: i32) -> RandomTable {
+        self.table(depth, true)
+    }
+
+    fn table(&self, depth: i32, level: bool) -> RandomTable {
+        self.prefabs
+            .iter()
+            .filter(|p| p.level == level)
+            .fold(RandomTable::new(), |table, p| {
+                table.add(&p.name, p.spawn.weight_at(depth))
+            })
+    }
+}
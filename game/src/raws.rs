@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use rltk::RGB;
+use serde::Deserialize;
+
+use crate::random_table::RandomTable;
+
+pub const RAWS_ENV: &str = "DD_RAWS";
+
+// built in so the game runs anywhere, DD_RAWS points at a file to use instead
+const SPAWNS_JSON: &str = include_str!("../raws/spawns.json");
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawRenderable {
+    pub glyph: String,
+    pub fg: String,
+    pub bg: String,
+    pub order: i32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawStats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawMovement {
+    pub inst: f32,
+}
+
+// how often something turns up, `depth_weight` is added to the weight for
+// every level deeper than `min_depth`
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawSpawn {
+    pub weight: i32,
+    pub min_depth: i32,
+    pub max_depth: Option<i32>,
+    #[serde(default)]
+    pub depth_weight: i32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawMonster {
+    pub name: String,
+    pub renderable: RawRenderable,
+    pub stats: RawStats,
+    pub vision_range: i32,
+    pub movement: Option<RawMovement>,
+    pub spawn: Option<RawSpawn>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawItem {
+    pub name: String,
+    pub renderable: RawRenderable,
+    #[serde(default)]
+    pub consumable: bool,
+    pub provides_healing: Option<i32>,
+    pub ranged: Option<i32>,
+    pub inflicts_damage: Option<i32>,
+    pub area_of_effect: Option<i32>,
+    pub spawn: Option<RawSpawn>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RawMaster {
+    pub monsters: Vec<RawMonster>,
+    pub items: Vec<RawItem>,
+}
+
+impl RawRenderable {
+    pub fn glyph(&self) -> rltk::FontCharType {
+        rltk::to_cp437(self.glyph.chars().next().unwrap())
+    }
+
+    pub fn fg(&self) -> RGB {
+        RGB::from_hex(&self.fg).unwrap()
+    }
+
+    pub fn bg(&self) -> RGB {
+        RGB::from_hex(&self.bg).unwrap()
+    }
+
+    fn validate(&self, name: &str, errors: &mut Vec<String>) {
+        if self.glyph.chars().count() != 1 {
+            errors.push(format!("{}: glyph must be a single character", name));
+        }
+        for color in [&self.fg, &self.bg] {
+            if RGB::from_hex(color).is_err() {
+                errors.push(format!("{}: {} is not a #RRGGBB color", name, color));
+            }
+        }
+    }
+}
+
+impl RawSpawn {
+    pub fn weight_at(&self, depth: i32) -> i32 {
+        if depth < self.min_depth || self.max_depth.is_some_and(|max| depth > max) {
+            return 0;
+        }
+        i32::max(
+            0,
+            self.weight + self.depth_weight * (depth - self.min_depth),
+        )
+    }
+
+    pub fn validate(&self, name: &str, errors: &mut Vec<String>) {
+        if self.weight < 0 {
+            errors.push(format!("{}: spawn weight can't be negative", name));
+        }
+        if self.min_depth < 1 {
+            errors.push(format!("{}: spawn min_depth must be at least 1", name));
+        }
+        if self.max_depth.is_some_and(|max| max < self.min_depth) {
+            errors.push(format!("{}: spawn max_depth is below min_depth", name));
+        }
+    }
+}
+
+impl RawMaster {
+    // reads the raws and refuses to start the game if they don't make sense
+    pub fn load() -> RawMaster {
+        let data = match env::var(RAWS_ENV) {
+            Ok(path) => fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Unable to read raws from {}: {}", path, e)),
+            Err(_) => SPAWNS_JSON.to_string(),
+        };
+
+        let raws: RawMaster =
+            serde_json::from_str(&data).unwrap_or_else(|e| panic!("Unable to parse raws: {}", e));
+
+        if let Err(errors) = raws.validate() {
+            panic!("Invalid raws:\n{}", errors.join("\n"));
+        }
+
+        raws
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut names = HashSet::new();
+
+        // the spawner picks from these, so neither list can be empty
+        if self.monsters.is_empty() {
+            errors.push("there must be at least one monster".to_string());
+        }
+        if self.items.is_empty() {
+            errors.push("there must be at least one item".to_string());
+        }
+
+        for monster in self.monsters.iter() {
+            if !names.insert(monster.name.clone()) {
+                errors.push(format!("{}: defined more than once", monster.name));
+            }
+            monster.renderable.validate(&monster.name, &mut errors);
+
+            let stats = &monster.stats;
+            if stats.max_hp <= 0 || stats.hp <= 0 || stats.hp > stats.max_hp {
+                errors.push(format!("{}: needs 0 < hp <= max_hp", monster.name));
+            }
+            if monster.vision_range <= 0 {
+                errors.push(format!("{}: vision_range must be positive", monster.name));
+            }
+            if let Some(movement) = &monster.movement {
+                if movement.inst <= 0.0 {
+                    errors.push(format!("{}: movement inst must be positive", monster.name));
+                }
+            }
+            if let Some(spawn) = &monster.spawn {
+                spawn.validate(&monster.name, &mut errors);
+            }
+        }
+
+        for item in self.items.iter() {
+            if !names.insert(item.name.clone()) {
+                errors.push(format!("{}: defined more than once", item.name));
+            }
+            item.renderable.validate(&item.name, &mut errors);
+
+            if item.area_of_effect.is_some() && item.inflicts_damage.is_none() {
+                errors.push(format!(
+                    "{}: area_of_effect needs inflicts_damage",
+                    item.name
+                ));
+            }
+            if item.inflicts_damage.is_some() && item.ranged.is_none() {
+                errors.push(format!("{}: inflicts_damage needs ranged", item.name));
+            }
+            for (field, value) in [
+                ("provides_healing", item.provides_healing),
+                ("ranged", item.ranged),
+                ("inflicts_damage", item.inflicts_damage),
+                ("area_of_effect", item.area_of_effect),
+            ] {
+                if value.is_some_and(|v| v <= 0) {
+                    errors.push(format!("{}: {} must be positive", item.name, field));
+                }
+            }
+            if let Some(spawn) = &item.spawn {
+                spawn.validate(&item.name, &mut errors);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn monster(&self, name: &str) -> Option<&RawMonster> {
+        self.monsters.iter().find(|m| m.name == name)
+    }
+
+    pub fn item(&self, name: &str) -> Option<&RawItem> {
+        self.items.iter().find(|i| i.name == name)
+    }
+
+    // what can show up at this depth, things without a spawn block only
+    // appear when asked for by name
+    pub fn monster_table(&self, depth: i32) -> RandomTable {
+        self.monsters
+            .iter()
+            .filter_map(|m| m.spawn.as_ref().map(|s| (&m.name, s.weight_at(depth))))
+            .fold(RandomTable::new(), |table, (name, weight)| {
+                table.add(name, weight)
+            })
+    }
+
+    pub fn item_table(&self, depth: i32) -> RandomTable {
+        self.items
+            .iter()
+            .filter_map(|i| i.spawn.as_ref().map(|s| (&i.name, s.weight_at(depth))))
+            .fold(RandomTable::new(), |table, (name, weight)| {
+                table.add(name, weight)
+            })
+    }
+}
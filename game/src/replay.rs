@@ -0,0 +1,133 @@
+use std::env;
+use std::fs::{self, File};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{physics_config::PhysicsConfig, player::PlayerAction};
+
+pub const REPLAY_ARG: &str = "--replay";
+pub const STEP_ARG: &str = "--step";
+
+// time between actions when a replay plays itself
+pub const REPLAY_STEP_MS: f32 = 150.0;
+
+// physics swapped in once this many actions had been taken
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PhysicsChange {
+    pub at: usize,
+    pub physics: PhysicsConfig,
+}
+
+// the seed, the physics and every action taken since, enough to rebuild a
+// run exactly
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub physics: PhysicsConfig,
+    pub physics_changes: Vec<PhysicsChange>,
+    pub actions: Vec<PlayerAction>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, physics: PhysicsConfig) -> Replay {
+        Replay {
+            seed,
+            physics,
+            physics_changes: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn record(ecs: &mut World, action: PlayerAction) {
+        ecs.fetch_mut::<Replay>().actions.push(action);
+    }
+
+    pub fn record_physics(ecs: &mut World, physics: &PhysicsConfig) {
+        let mut replay = ecs.fetch_mut::<Replay>();
+        let at = replay.actions.len();
+        replay.physics_changes.push(PhysicsChange {
+            at,
+            physics: physics.clone(),
+        });
+    }
+}
+
+// a replay being fed back through the game
+pub struct Playback {
+    pub actions: Vec<PlayerAction>,
+    pub physics_changes: Vec<PhysicsChange>,
+    pub index: usize,
+    pub step: bool,
+    pub timer: f32,
+}
+
+impl Playback {
+    pub fn new(replay: Replay, step: bool) -> Playback {
+        Playback {
+            actions: replay.actions,
+            physics_changes: replay.physics_changes,
+            index: 0,
+            step,
+            timer: 0.0,
+        }
+    }
+
+    // the physics in play before the next action, when they changed
+    pub fn physics_due(&self) -> Option<PhysicsConfig> {
+        self.physics_changes
+            .iter()
+            .rev()
+            .find(|c| c.at == self.index)
+            .map(|c| c.physics.clone())
+    }
+
+    pub fn next_action(&mut self) -> Option<PlayerAction> {
+        let action = self.actions.get(self.index).copied();
+        self.index += 1;
+        action
+    }
+}
+
+// nothing is saved on the web, and there is no clock to name files by
+#[cfg(target_arch = "wasm32")]
+pub fn replay_file(seed: u64) -> String {
+    format!("./replay-{}.json", seed)
+}
+
+// each run is recorded to its own file, named for its seed and when it began
+#[cfg(not(target_arch = "wasm32"))]
+pub fn replay_file(seed: u64) -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format!("./replay-{}-{}.json", seed, started)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_replay(_ecs: &World, _path: &str) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_replay(ecs: &World, path: &str) {
+    let writer = File::create(path).expect("Unable to create replay file");
+    serde_json::to_writer(writer, &*ecs.fetch::<Replay>()).expect("Unable to write replay");
+}
+
+pub fn load_replay(path: &str) -> Replay {
+    let data = fs::read_to_string(path).expect("Unable to read replay file");
+    serde_json::from_str(&data).expect("Unable to parse replay file")
+}
+
+// `--replay <file>` plays a recorded run back, `--step` waits for a key
+// between actions
+pub fn playback_from_args() -> Option<(Replay, bool)> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .iter()
+        .position(|a| a == REPLAY_ARG)
+        .and_then(|i| args.get(i + 1))?;
+
+    Some((load_replay(path), args.iter().any(|a| a == STEP_ARG)))
+}
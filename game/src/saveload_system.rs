@@ -0,0 +1,256 @@
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::path::Path;
+
+use rltk::Point;
+use specs::prelude::*;
+use specs::saveload::{
+    DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+};
+
+use crate::components::*;
+use crate::gamelog::GameLog;
+use crate::map::{Map, MAPCOUNT};
+use crate::physics_config::PhysicsConfig;
+use crate::player::Player;
+use crate::replay::Replay;
+use crate::seed::WorldSeed;
+use crate::state::register_components;
+
+pub const SAVE_FILE: &str = "./savegame.json";
+const MISSING_HELPER: &str = "The save file is missing its resources";
+
+// serializes each listed component storage in turn
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+// deserializes the component storages in the order they were written
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<Infallible, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0,
+            &mut $data.1,
+            &mut $data.2,
+            &mut $de,
+        )
+        .map_err(|e| format!("Unable to load {}: {}", stringify!($type), e))?;
+        )*
+    };
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_game(_ecs: &mut World) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_game(ecs: &mut World) {
+    // stash the resources in a helper entity so they are saved with the rest
+    let map_copy = ecs.get_mut::<Map>().unwrap().clone();
+    let log_copy = ecs.get_mut::<GameLog>().unwrap().clone();
+    let seed_copy = *ecs.fetch::<WorldSeed>();
+    let physics_copy = ecs.get_mut::<PhysicsConfig>().unwrap().clone();
+    let replay_copy = ecs.get_mut::<Replay>().unwrap().clone();
+    let save_helper = ecs
+        .create_entity()
+        .with(SerializationHelper {
+            map: map_copy,
+            log: log_copy,
+            seed: seed_copy,
+            physics: physics_copy,
+            replay: replay_copy,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    {
+        let data = (
+            ecs.entities(),
+            ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+        );
+
+        let writer = File::create(SAVE_FILE).expect("Unable to create save file");
+        let mut serializer = serde_json::Serializer::new(writer);
+        serialize_individually!(
+            ecs,
+            serializer,
+            data,
+            Position,
+            Renderable,
+            Player,
+            Viewshed,
+            Monster,
+            Name,
+            BlocksTile,
+            CombatStats,
+            WantsToMelee,
+            WantsToCollide,
+            SufferDamage,
+            Item,
+            ProvidesHealing,
+            InBackpack,
+            WantsToPickUpItem,
+            WantsToUseItem,
+            WantsToDropItem,
+            Consumable,
+            Ranged,
+            InflictsDamage,
+            AreaOfEffect,
+            Velocity,
+            InstVel,
+            Balance,
+            WantsToFallover,
+            Grinding,
+            Package,
+            DeliveryTarget,
+            Courier,
+            CargoDamage,
+            SerializationHelper
+        );
+    }
+
+    ecs.delete_entity(save_helper)
+        .expect("Unable to delete save helper");
+}
+
+pub fn does_save_exist() -> bool {
+    Path::new(SAVE_FILE).exists()
+}
+
+// reads every saved component into ecs, stopping at the first that won't load
+fn read_save(ecs: &mut World, data: &str) -> Result<(), String> {
+    let mut de = serde_json::Deserializer::from_str(data);
+    let mut d = (
+        &mut ecs.entities(),
+        &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+        &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
+    );
+
+    deserialize_individually!(
+        ecs,
+        de,
+        d,
+        Position,
+        Renderable,
+        Player,
+        Viewshed,
+        Monster,
+        Name,
+        BlocksTile,
+        CombatStats,
+        WantsToMelee,
+        WantsToCollide,
+        SufferDamage,
+        Item,
+        ProvidesHealing,
+        InBackpack,
+        WantsToPickUpItem,
+        WantsToUseItem,
+        WantsToDropItem,
+        Consumable,
+        Ranged,
+        InflictsDamage,
+        AreaOfEffect,
+        Velocity,
+        InstVel,
+        Balance,
+        WantsToFallover,
+        Grinding,
+        Package,
+        DeliveryTarget,
+        Courier,
+        CargoDamage,
+        SerializationHelper
+    );
+
+    Ok(())
+}
+
+// a save that can't be read, or is missing its resources, is an error and
+// leaves the world as it was
+pub fn load_game(ecs: &mut World) -> Result<(), String> {
+    let data =
+        fs::read_to_string(SAVE_FILE).map_err(|e| format!("Unable to read save file: {}", e))?;
+
+    // try it out on a world of its own before throwing the current one away
+    {
+        let mut scratch = World::new();
+        register_components(&mut scratch);
+        scratch.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        read_save(&mut scratch, &data)?;
+        if scratch
+            .read_storage::<SerializationHelper>()
+            .join()
+            .next()
+            .is_none()
+        {
+            return Err(MISSING_HELPER.to_string());
+        }
+    }
+
+    // start from an empty world
+    {
+        let mut to_delete = Vec::new();
+        for e in ecs.entities().join() {
+            to_delete.push(e);
+        }
+        for del in to_delete.iter() {
+            ecs.delete_entity(*del).expect("Unable to delete entity");
+        }
+    }
+
+    read_save(ecs, &data)?;
+
+    // put the saved resources back in place
+    let mut delete_me: Option<Entity> = None;
+    {
+        let entities = ecs.entities();
+        let helper = ecs.read_storage::<SerializationHelper>();
+        let player = ecs.read_storage::<Player>();
+        let position = ecs.read_storage::<Position>();
+
+        for (e, h) in (&entities, &helper).join() {
+            let mut worldmap = ecs.write_resource::<Map>();
+            *worldmap = h.map.clone();
+            worldmap.tile_content = vec![Vec::new(); MAPCOUNT];
+
+            let mut log = ecs.write_resource::<GameLog>();
+            *log = h.log.clone();
+
+            let mut seed = ecs.write_resource::<WorldSeed>();
+            *seed = h.seed;
+
+            let mut physics = ecs.write_resource::<PhysicsConfig>();
+            *physics = h.physics.clone();
+
+            let mut replay = ecs.write_resource::<Replay>();
+            *replay = h.replay.clone();
+
+            delete_me = Some(e);
+        }
+
+        for (e, _p, pos) in (&entities, &player, &position).join() {
+            let mut player_pos = ecs.write_resource::<Point>();
+            *player_pos = pos.point;
+
+            let mut player_resource = ecs.write_resource::<Entity>();
+            *player_resource = e;
+        }
+    }
+
+    let save_helper = delete_me.ok_or(MISSING_HELPER)?;
+    ecs.delete_entity(save_helper)
+        .expect("Unable to delete save helper");
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::env;
+
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+pub const SEED_ENV: &str = "DD_SEED";
+pub const SEED_ARG: &str = "--seed";
+
+// the seed the current run's levels are generated from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct WorldSeed {
+    pub seed: u64,
+}
+
+impl WorldSeed {
+    // a fresh seed, kept small so it is easy to read off the HUD
+    pub fn random() -> WorldSeed {
+        WorldSeed {
+            seed: RandomNumberGenerator::new().next_u64() % u32::MAX as u64,
+        }
+    }
+
+    // `--seed <n>` on the command line wins over the environment
+    pub fn from_args() -> Option<WorldSeed> {
+        let args = env::args().collect::<Vec<_>>();
+        let arg = args
+            .iter()
+            .position(|a| a == SEED_ARG)
+            .and_then(|i| args.get(i + 1).cloned());
+
+        arg.or_else(|| env::var(SEED_ENV).ok())
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .map(|seed| WorldSeed { seed })
+    }
+
+    // each depth gets its own stream so a level only depends on the seed
+    // and how deep it is, not on what happened on the levels before it
+    pub fn depth_rng(&self, depth: i32) -> RandomNumberGenerator {
+        let mixed = self
+            .seed
+            .wrapping_add((depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        RandomNumberGenerator::seeded(mixed)
+    }
+}
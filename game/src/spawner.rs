@@ -1,20 +1,30 @@
 use std::collections::HashSet;
 
-use rltk::{Point, PointF, RandomNumberGenerator, Rect, RGB};
+use rltk::{Point, PointF, RandomNumberGenerator, RGB};
 use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
 
 use crate::{
     components::{
-        AreaOfEffect, Balance, BlocksTile, CombatStats, Consumable, InflictsDamage, Item, Monster,
-        Name, Position, ProvidesHealing, Ranged, Renderable, Velocity, Viewshed,
+        AreaOfEffect, Balance, BlocksTile, CombatStats, Consumable, Courier, DeliveryTarget,
+        InflictsDamage, Item, Monster, Name, Package, Position, ProvidesHealing, Ranged,
+        Renderable, SerializeMe, Velocity, Viewshed,
     },
-    map::MAPWIDTH,
+    delivery_system::MAX_CONDITION,
+    map::{Map, TileType, MAPWIDTH},
     player::Player,
+    raws::{RawItem, RawMaster, RawMonster},
 };
 
 pub const MAX_MONSTERS: i32 = 4;
 pub const MAX_ITEMS: i32 = 4;
 
+pub const BASE_REWARD: i32 = 10;
+pub const DEPTH_REWARD: i32 = 5;
+
+// packages and drop-offs rolled before giving up on a job for the level
+pub const MAX_DELIVERY_TRIES: i32 = 20;
+
 pub fn spawn_player(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position {
@@ -46,53 +56,72 @@ pub fn spawn_player(ecs: &mut World, x: i32, y: i32) -> Entity {
         .with(Balance {
             bal: PointF::new(0.0, 0.0),
         })
+        .with(Courier {
+            earnings: 0,
+            deliveries: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
+// rolls on the monster table for the current depth
 pub fn random_monster(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
+    let name;
     {
+        let raws = ecs.fetch::<RawMaster>();
+        let map = ecs.fetch::<Map>();
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 2);
+        name = raws.monster_table(map.depth).roll(&mut rng);
     }
-    match roll {
-        1 => orc(ecs, x, y),
-        _ => goblin(ecs, x, y),
+    if let Some(name) = name {
+        spawn_named(ecs, &name, x, y);
     }
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('o'), "Orc");
-}
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('g'), "Goblin");
+// builds the monster or item called `name` from the raws
+pub fn spawn_named(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    let (monster, item);
+    {
+        let raws = ecs.fetch::<RawMaster>();
+        monster = raws.monster(name).cloned();
+        item = raws.item(name).cloned();
+    }
+
+    if let Some(monster) = monster {
+        return Some(spawn_monster(ecs, &monster, x, y));
+    }
+    if let Some(item) = item {
+        return Some(spawn_item(ecs, &item, x, y));
+    }
+
+    None
 }
 
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S) {
+fn spawn_monster(ecs: &mut World, raw: &RawMonster, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position {
             point: Point::new(x, y),
         })
         .with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1,
+            glyph: raw.renderable.glyph(),
+            fg: raw.renderable.fg(),
+            bg: raw.renderable.bg(),
+            render_order: raw.renderable.order,
         })
         .with(Viewshed {
             visible_tiles: HashSet::new(),
-            range: 8,
+            range: raw.vision_range,
         })
         .with(Monster {})
         .with(Name {
-            name: name.to_string(),
+            name: raw.name.clone(),
         })
         .with(BlocksTile {})
         .with(CombatStats {
-            max_hp: 16,
-            hp: 16,
-            defense: 1,
-            power: 4,
+            max_hp: raw.stats.max_hp,
+            hp: raw.stats.hp,
+            defense: raw.stats.defense,
+            power: raw.stats.power,
         })
         .with(Velocity {
             vel: PointF::new(0.0, 0.0),
@@ -100,10 +129,13 @@ fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: rltk::FontCharTy
         .with(Balance {
             bal: PointF::new(0.0, 0.0),
         })
-        .build();
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-pub fn spawn_room(ecs: &mut World, room: &Rect) {
+// populates an area of any shape, given as map indices
+pub fn spawn_region(ecs: &mut World, area: &[usize]) {
+    let mut tiles = area.to_vec();
     let mut monster_spawn_points = Vec::new();
     let mut item_spawn_points = Vec::new();
 
@@ -112,17 +144,16 @@ pub fn spawn_room(ecs: &mut World, room: &Rect) {
         let num_monsters = rng.roll_dice(1, MAX_MONSTERS + 2) - 3;
         let num_items = rng.roll_dice(1, MAX_ITEMS + 2) - 3;
 
-        for _ in 0..num_monsters {
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1 - 1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1 - 1))) as usize;
-            let idx = (y * MAPWIDTH) + x;
-            monster_spawn_points.push(idx);
-        }
-        for _ in 0..num_items {
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1 - 1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1 - 1))) as usize;
-            let idx = (y * MAPWIDTH) + x;
-            item_spawn_points.push(idx);
+        for i in 0..num_monsters + num_items {
+            if tiles.is_empty() {
+                break;
+            }
+            let idx = tiles.remove(rng.roll_dice(1, tiles.len() as i32) as usize - 1);
+            if i < num_monsters {
+                monster_spawn_points.push(idx);
+            } else {
+                item_spawn_points.push(idx);
+            }
         }
     }
 
@@ -138,84 +169,176 @@ pub fn spawn_room(ecs: &mut World, room: &Rect) {
     }
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+fn spawn_item(ecs: &mut World, raw: &RawItem, x: i32, y: i32) -> Entity {
+    let mut builder = ecs
+        .create_entity()
         .with(Position {
             point: Point::new(x, y),
         })
         .with(Renderable {
-            glyph: rltk::to_cp437('i'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
+            glyph: raw.renderable.glyph(),
+            fg: raw.renderable.fg(),
+            bg: raw.renderable.bg(),
+            render_order: raw.renderable.order,
         })
         .with(Name {
-            name: "Health Potion".to_string(),
+            name: raw.name.clone(),
         })
-        .with(Item {})
-        .with(Consumable {})
-        .with(ProvidesHealing { heal_amount: 8 })
-        .build();
+        .with(Item {});
+
+    if raw.consumable {
+        builder = builder.with(Consumable {});
+    }
+    if let Some(heal_amount) = raw.provides_healing {
+        builder = builder.with(ProvidesHealing { heal_amount });
+    }
+    if let Some(range) = raw.ranged {
+        builder = builder.with(Ranged { range });
+    }
+    if let Some(damage) = raw.inflicts_damage {
+        builder = builder.with(InflictsDamage { damage });
+    }
+    if let Some(radius) = raw.area_of_effect {
+        builder = builder.with(AreaOfEffect { radius });
+    }
+
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+// rolls on the item table for the current depth
+pub fn random_item(ecs: &mut World, x: i32, y: i32) {
+    let name;
+    {
+        let raws = ecs.fetch::<RawMaster>();
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        name = raws.item_table(map.depth).roll(&mut rng);
+    }
+    if let Some(name) = name {
+        spawn_named(ecs, &name, x, y);
+    }
+}
+
+// places a package somewhere the player can reach and its destination
+// in the farther half of the level from the package, both somewhere we can
+// still get to the stairs from
+pub fn spawn_delivery(ecs: &mut World, start: Point) {
+    let (pickup, dropoff, depth);
+    {
+        let mut map = ecs.fetch_mut::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        depth = map.depth;
+        // only the walls, whatever the builders last left blocked
+        map.blocked_tiles.clear();
+        map.populate_blocked();
+
+        let map_starts = vec![map.xy_flat(start.x, start.y)];
+        let dijkstra_map =
+            rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+        let reachable = map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| {
+                **t == TileType::Floor && dijkstra_map.map[*i] != f32::MAX && *i != map_starts[0]
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+
+        if reachable.len() < 2 {
+            return;
+        }
+        let stairs = match map.tiles.iter().position(|t| *t == TileType::DownStairs) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        // we can drop off ledges we can't climb back up, so wherever the job
+        // takes us has to leave a way on to the stairs
+        let reaches_stairs = |idx: usize| {
+            let map_starts = vec![idx];
+            let dijkstra_map =
+                rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+            dijkstra_map.map[stairs] != f32::MAX
+        };
+
+        let mut job = None;
+        for _ in 0..MAX_DELIVERY_TRIES {
+            let pickup = reachable[rng.roll_dice(1, reachable.len() as i32) as usize - 1];
+            if !reaches_stairs(pickup) {
+                continue;
+            }
+
+            let map_starts = vec![pickup];
+            let dijkstra_map =
+                rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, 1000.0);
+            let mut by_distance = reachable
+                .iter()
+                .filter(|i| **i != pickup && dijkstra_map.map[**i] != f32::MAX)
+                .map(|i| (*i, dijkstra_map.map[*i]))
+                .collect::<Vec<(usize, f32)>>();
+            if by_distance.is_empty() {
+                continue;
+            }
+            by_distance.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+            let far_half = &by_distance[by_distance.len() / 2..];
+            let dropoff = far_half[rng.roll_dice(1, far_half.len() as i32) as usize - 1].0;
+            if reaches_stairs(dropoff) {
+                job = Some((pickup, dropoff));
+                break;
+            }
+        }
+
+        match job {
+            Some(job) => (pickup, dropoff) = job,
+            None => return,
+        }
+    }
+
+    let target = ecs
+        .create_entity()
         .with(Position {
-            point: Point::new(x, y),
+            point: Point::new(
+                dropoff as i32 % MAPWIDTH as i32,
+                dropoff as i32 / MAPWIDTH as i32,
+            ),
         })
         .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
+            glyph: rltk::to_cp437('◙'),
+            fg: RGB::named(rltk::GREEN),
             bg: RGB::named(rltk::BLACK),
-            render_order: 2,
+            render_order: 3,
         })
         .with(Name {
-            name: "Magic Missile Scroll".to_string(),
+            name: "Drop-off Point".to_string(),
         })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
+        .with(DeliveryTarget {})
+        .marked::<SimpleMarker<SerializeMe>>()
         .build();
-}
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
     ecs.create_entity()
         .with(Position {
-            point: Point::new(x, y),
+            point: Point::new(
+                pickup as i32 % MAPWIDTH as i32,
+                pickup as i32 / MAPWIDTH as i32,
+            ),
         })
         .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::ORANGE),
+            glyph: rltk::to_cp437('■'),
+            fg: RGB::named(rltk::BURLYWOOD),
             bg: RGB::named(rltk::BLACK),
             render_order: 2,
         })
         .with(Name {
-            name: "Fireball Scroll".to_string(),
+            name: "Package".to_string(),
         })
         .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 20 })
-        .with(AreaOfEffect { radius: 3 })
+        .with(Package {
+            destination: target,
+            reward: BASE_REWARD + depth * DEPTH_REWARD,
+            condition: MAX_CONDITION,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
-
-fn random_item(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 3);
-    }
-    match roll {
-        1 => {
-            return health_potion(ecs, x, y);
-        }
-        2 => {
-            return magic_missile_scroll(ecs, x, y);
-        }
-        _ => {
-            return fireball_scroll(ecs, x, y);
-        }
-    }
-}
\ No newline at end of file
@@ -1,25 +1,31 @@
-use rltk::{GameState, Point, Rltk};
+use rltk::{console, GameState, Point, Rltk, VirtualKeyCode};
 
 use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
 
-use crate::components::{
-    CombatStats, InBackpack, Ranged, Viewshed, WantsToDropItem, WantsToUseItem,
-};
+use crate::collision_system::CollisionSystem;
+use crate::components::*;
 use crate::damage_system::DamageSystem;
+use crate::delivery_system::DeliverySystem;
 use crate::gamelog::GameLog;
 use crate::gui::{self};
-use crate::gui::{ItemMenuResult, MainMenuSelection};
+use crate::gui::{ItemMenuResult, MainMenuSelection, TrajectoryPreview};
 use crate::inventory_system::{ItemCollectionSystem, ItemUseSystem};
 use crate::item_drop_system::ItemDropSystem;
 use crate::map::{self, Map, MAPHEIGHT, MAPWIDTH};
 use crate::map_indexing_system::MapIndexingSystem;
+use crate::melee_combat_system::MeleeCombatSystem;
+use crate::monster_ai_system::MonsterAI;
 use crate::movement_system::{FalloverSystem, MovementSystem, VelocityBalanceSystem};
-use crate::player::{look_mode_input, ranged_targeting_input, Player};
+use crate::physics_config::PhysicsConfig;
+use crate::player::{self, look_mode_input, ranged_targeting_input, Player, PlayerAction};
+use crate::prefabs::PrefabMaster;
+use crate::raws::RawMaster;
+use crate::replay::{self, Playback, Replay, REPLAY_STEP_MS};
+use crate::seed::WorldSeed;
 use crate::visibility_system::VisibilitySystem;
-use crate::{help_viewer, map_builders, SHOW_MAPGEN_VISUALIZER};
+use crate::{help_viewer, map_builders, saveload_system, spawner, SHOW_MAPGEN_VISUALIZER};
 
-use super::components::Position;
-use super::components::Renderable;
 use super::player::player_input;
 
 const REVEAL_MAP: bool = true;
@@ -48,6 +54,7 @@ pub enum RunState {
     },
     NextLevel,
     MapGeneration,
+    Replaying,
 }
 
 pub struct State {
@@ -59,6 +66,12 @@ pub struct State {
     pub redraw_targeting: bool,
     pub draw_inventory: bool,
 
+    // what the trajectory overlay projects, if anything
+    pub trajectory_preview: TrajectoryPreview,
+
+    // whether a game is running behind the main menu
+    pub in_game: bool,
+
     // lookmode variables
     pub look_cursor: (i32, i32),
     pub last_mouse_position: (i32, i32),
@@ -68,33 +81,140 @@ pub struct State {
     pub mapgen_history: Vec<Map>,            // copy of the mapgen history
     pub mapgen_index: usize,                 // current index into mapgen history
     pub mapgen_timer: f32,                   // times mapgen animation
+
+    // replay variables
+    pub replay_file: Option<String>, // where the run is recorded to
+    pub playback: Option<Playback>,  // set while a replay plays back
+
+    pub autosave: bool, // save after every turn and on reaching a new level
+}
+
+// every component the game uses, in a world of its own or the game's
+pub fn register_components(ecs: &mut World) {
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Monster>();
+    ecs.register::<Name>();
+    ecs.register::<BlocksTile>();
+    ecs.register::<CombatStats>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<WantsToCollide>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Item>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<InBackpack>();
+    ecs.register::<WantsToPickUpItem>();
+    ecs.register::<WantsToUseItem>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<Consumable>();
+    ecs.register::<Ranged>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<Velocity>();
+    ecs.register::<InstVel>();
+    ecs.register::<Balance>();
+    ecs.register::<WantsToFallover>();
+    ecs.register::<Grinding>();
+    ecs.register::<Package>();
+    ecs.register::<DeliveryTarget>();
+    ecs.register::<Courier>();
+    ecs.register::<CargoDamage>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<SerializationHelper>();
 }
 
 impl State {
+    // builds the world with every component registered and the resources a
+    // game needs, no window required
+    pub fn new(seed: WorldSeed) -> State {
+        let mut state = State {
+            ecs: World::new(),
+            map_drawn: false,
+            redraw_menu: true,
+            redraw_targeting: true,
+            draw_inventory: false,
+            trajectory_preview: TrajectoryPreview::Off,
+            in_game: false,
+            look_cursor: (-1, -1),
+            last_mouse_position: (-1, -1),
+            mapgen_next_state: Some(RunState::MainMenu {
+                menu_selection: MainMenuSelection::NewGame,
+            }),
+            mapgen_history: Vec::new(),
+            mapgen_index: 0,
+            mapgen_timer: 0.0,
+            replay_file: None,
+            playback: None,
+            autosave: false,
+        };
+
+        register_components(&mut state.ecs);
+
+        state
+            .ecs
+            .insert(SimpleMarkerAllocator::<SerializeMe>::new());
+
+        let raws = RawMaster::load();
+        let physics = PhysicsConfig::load(&raws);
+        state
+            .ecs
+            .insert(PrefabMaster::load(&raws, &physics.player()));
+        state.ecs.insert(physics);
+        state.ecs.insert(raws);
+
+        let player_entity = spawner::spawn_player(&mut state.ecs, 0, 0);
+
+        state.ecs.insert(seed);
+        state.ecs.insert(seed.depth_rng(1));
+        let physics = (*state.ecs.fetch::<PhysicsConfig>()).clone();
+        state.ecs.insert(Replay::new(seed.seed, physics));
+        state.ecs.insert(RunState::MapGeneration);
+        state.ecs.insert(player_entity);
+        state.ecs.insert(Map::new(1));
+        state.ecs.insert(Point::new(0, 0));
+        state.ecs.insert(GameLog {
+            entries: vec!["Welcome to Dangerous Deliveries".to_string()],
+        });
+
+        state
+    }
+
     pub fn run_systems_player(&mut self) {
         let mut vis = VisibilitySystem {};
         let mut map_index = MapIndexingSystem {};
         let mut pickup = ItemCollectionSystem {};
         let mut drop_system = ItemDropSystem {};
         let mut item_use_system = ItemUseSystem {};
+        let mut monster_ai = MonsterAI {};
         let mut speed_balance = VelocityBalanceSystem {};
         let mut move_system = MovementSystem {};
+        let mut collision_system = CollisionSystem {};
         let mut fallover_system = FalloverSystem {};
+        let mut melee_system = MeleeCombatSystem {};
+        let mut delivery_system = DeliverySystem {};
         let mut damage_system = DamageSystem {};
 
         item_use_system.run_now(&self.ecs);
 
-        damage_system.run_now(&self.ecs);
-
         pickup.run_now(&self.ecs);
         drop_system.run_now(&self.ecs);
 
+        monster_ai.run_now(&self.ecs);
+
         speed_balance.run_now(&self.ecs);
         fallover_system.run_now(&self.ecs);
 
         move_system.run_now(&self.ecs);
+        collision_system.run_now(&self.ecs);
         fallover_system.run_now(&self.ecs);
 
+        melee_system.run_now(&self.ecs);
+        damage_system.run_now(&self.ecs);
+
+        delivery_system.run_now(&self.ecs);
+
         vis.run_now(&self.ecs);
 
         map_index.run_now(&self.ecs);
@@ -159,14 +279,49 @@ impl State {
         }
     }
 
+    // throws away the current run and starts over at depth 1
+    fn new_game(&mut self) -> RunState {
+        let to_delete = self.ecs.entities().join().collect::<Vec<_>>();
+        for target in to_delete {
+            self.ecs
+                .delete_entity(target)
+                .expect("Unable to delete entity");
+        }
+
+        let player_entity = spawner::spawn_player(&mut self.ecs, 0, 0);
+        self.ecs.insert(player_entity);
+        let seed = WorldSeed::from_args().unwrap_or_else(WorldSeed::random);
+        self.ecs.insert(seed);
+        let physics = (*self.ecs.fetch::<PhysicsConfig>()).clone();
+        self.ecs.insert(Replay::new(seed.seed, physics));
+        if self.replay_file.is_some() {
+            self.replay_file = Some(replay::replay_file(seed.seed));
+        }
+        self.ecs.insert(GameLog {
+            entries: vec!["Welcome to Dangerous Deliveries".to_string()],
+        });
+
+        self.generate_world_map(1);
+        self.mapgen_next_state = Some(RunState::PreRun);
+
+        RunState::MapGeneration
+    }
+
     pub fn generate_world_map(&mut self, new_depth: i32) {
         // reset mapgen vars
         self.mapgen_history.clear();
         self.mapgen_index = 0;
         self.mapgen_timer = 0.0;
 
-        let mut builder = map_builders::random_builder(new_depth);
-        builder.build_map(&mut self.ecs);
+        // the same seed and depth always build the same level
+        let mut rng = self.ecs.fetch::<WorldSeed>().depth_rng(new_depth);
+
+        let builder = {
+            let prefabs = self.ecs.fetch::<PrefabMaster>();
+            let physics = self.ecs.fetch::<PhysicsConfig>().player();
+            map_builders::build_level(new_depth, &mut rng, &prefabs, &physics)
+        };
+        self.ecs.insert(rng);
 
         // clone mapgen history from new map
         self.mapgen_history = builder.get_snapshot_history();
@@ -183,6 +338,7 @@ impl State {
         }
 
         builder.spawn_entities(&mut self.ecs);
+        spawner::spawn_delivery(&mut self.ecs, player_start);
 
         let mut position_components = self.ecs.write_storage::<Position>();
         let player_entity = self.ecs.fetch::<Entity>();
@@ -214,26 +370,89 @@ impl State {
         }
     }
 
-    fn tick_prerun(&mut self) -> RunState {
+    pub fn tick_prerun(&mut self) -> RunState {
         self.run_systems_player();
         self.map_drawn = false;
+        self.in_game = true;
 
-        RunState::AwaitingInput
+        self.next_input_state()
     }
 
-    fn tick_player_turn(&mut self) -> RunState {
+    pub fn tick_player_turn(&mut self) -> RunState {
         self.run_systems_player();
         map::cleanup_dead(&mut self.ecs);
         self.map_drawn = false;
-        RunState::AwaitingInput
+        self.save_replay();
+        self.autosave_game();
+        self.next_input_state()
     }
 
-    fn tick_next_level(&mut self) -> RunState {
+    pub fn tick_next_level(&mut self) -> RunState {
         let current_depth = self.ecs.read_resource::<Map>().depth;
         self.goto_next_level(current_depth + 1);
+        self.save_replay();
+        self.autosave_game();
         RunState::PreRun
     }
 
+    // input comes from the player, or from the replay while one plays
+    fn next_input_state(&self) -> RunState {
+        if self.playback.is_some() {
+            RunState::Replaying
+        } else {
+            RunState::AwaitingInput
+        }
+    }
+
+    fn save_replay(&self) {
+        if let Some(path) = &self.replay_file {
+            replay::save_replay(&self.ecs, path);
+        }
+    }
+
+    fn autosave_game(&mut self) {
+        if self.autosave {
+            saveload_system::save_game(&mut self.ecs);
+        }
+    }
+
+    fn tick_replaying(&mut self, ctx: &mut Rltk) -> RunState {
+        // escape hands control back to the player
+        if ctx.key == Some(VirtualKeyCode::Escape) {
+            self.playback = None;
+            return RunState::AwaitingInput;
+        }
+
+        let playback = self.playback.as_mut().unwrap();
+        if playback.step {
+            if ctx.key.is_none() {
+                return RunState::Replaying;
+            }
+        } else {
+            playback.timer += ctx.frame_time_ms;
+            if playback.timer < REPLAY_STEP_MS {
+                return RunState::Replaying;
+            }
+            playback.timer = 0.0;
+        }
+
+        if let Some(physics) = playback.physics_due() {
+            PhysicsConfig::apply(&mut self.ecs, physics);
+        }
+        match playback.next_action() {
+            Some(action) => player::perform_action(&mut self.ecs, action),
+            None => {
+                self.playback = None;
+                self.map_drawn = false;
+                self.ecs
+                    .fetch_mut::<GameLog>()
+                    .entries
+                    .push("The replay is over".to_string());
+                RunState::AwaitingInput
+            }
+        }
+    }
+
     fn tick_looking(&mut self, ctx: &mut Rltk) -> RunState {
         if self.last_mouse_position.0 == -1 {
             self.last_mouse_position = ctx.mouse_pos();
@@ -273,21 +492,16 @@ impl State {
                         item: item_entity,
                         cursor: *player_pos,
                     };
-                } else {
-                    let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                    intent
-                        .insert(
-                            *self.ecs.fetch::<Entity>(),
-                            WantsToUseItem {
-                                item: item_entity,
-                                target: None,
-                            },
-                        )
-                        .expect("Unable to insert intent to use item");
-                    self.map_drawn = false;
-
-                    return RunState::PlayerTurn;
                 }
+                drop(ranged_items);
+
+                let slot = player::backpack_slot(&self.ecs, item_entity).unwrap();
+                self.map_drawn = false;
+
+                return player::perform_action(
+                    &mut self.ecs,
+                    PlayerAction::UseItem { slot, target: None },
+                );
             }
         }
     }
@@ -304,16 +518,10 @@ impl State {
             }
             gui::ItemMenuResult::Selected => {
                 let item_entity = result.1.unwrap();
-                let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                intent
-                    .insert(
-                        *self.ecs.fetch::<Entity>(),
-                        WantsToDropItem { item: item_entity },
-                    )
-                    .expect("Unable to insert intent to drop item");
+                let slot = player::backpack_slot(&self.ecs, item_entity).unwrap();
 
                 self.map_drawn = false;
-                return RunState::PlayerTurn;
+                return player::perform_action(&mut self.ecs, PlayerAction::DropItem { slot });
             }
         }
     }
@@ -345,19 +553,14 @@ impl State {
                 return RunState::AwaitingInput;
             }
             ItemMenuResult::Selected => {
-                let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                intent
-                    .insert(
-                        *self.ecs.fetch::<Entity>(),
-                        WantsToUseItem {
-                            item,
-                            target: selection.1,
-                        },
-                    )
-                    .expect("Unable to insert intent to use ranged item");
+                let slot = player::backpack_slot(&self.ecs, item).unwrap();
+                let target = selection.1;
 
                 self.map_drawn = false;
-                return RunState::PlayerTurn;
+                return player::perform_action(
+                    &mut self.ecs,
+                    PlayerAction::UseItem { slot, target },
+                );
             }
         }
     }
@@ -380,8 +583,32 @@ impl State {
                 }
                 match selected {
                     gui::MainMenuSelection::NewGame => {
+                        if self.in_game {
+                            return self.new_game();
+                        }
                         return RunState::PreRun;
                     }
+                    gui::MainMenuSelection::Continue => {
+                        if let Err(e) = saveload_system::load_game(&mut self.ecs) {
+                            console::log(e);
+                            self.redraw_menu = true;
+                            return RunState::MainMenu {
+                                menu_selection: selected,
+                            };
+                        }
+
+                        let mut map_index = MapIndexingSystem {};
+                        map_index.run_now(&self.ecs);
+                        self.ecs.maintain();
+
+                        self.in_game = true;
+                        self.map_drawn = false;
+                        RunState::AwaitingInput
+                    }
+                    gui::MainMenuSelection::SaveAndQuit => {
+                        saveload_system::save_game(&mut self.ecs);
+                        ::std::process::exit(0);
+                    }
                     gui::MainMenuSelection::Quit => {
                         ::std::process::exit(0);
                     }
@@ -477,6 +704,9 @@ impl GameState for State {
             RunState::MapGeneration => {
                 newrunstate = self.tick_map_generation(ctx);
             }
+            RunState::Replaying => {
+                newrunstate = self.tick_replaying(ctx);
+            }
         } // done determining newrunstate
 
         {
@@ -532,6 +762,7 @@ impl GameState for State {
                 }
             }
 
+            gui::draw_trajectory(&self.ecs, ctx, self.trajectory_preview);
             gui::draw_ui(&self.ecs, ctx);
 
             if newrunstate == RunState::Looking && moved_look_cursor {